@@ -1,3 +1,8 @@
 docker build . --tag ppcli
 docker run -v ${HOME}/ppcli_static:/host ppcli:latest
 echo "A ppcli static binary was generated in ${HOME}/ppcli_static"
+# the release assets: ppcli --upgrade verifies the archive with the .sha256 file
+VERSION=$(grep '^version' ppcli/Cargo.toml | cut -d '"' -f 2)
+ARCHIVE=ppcli-${VERSION}-linux_x86_64.tgz
+(cd ${HOME}/ppcli_static && tar czf ${ARCHIVE} ppcli && sha256sum ${ARCHIVE} > ${ARCHIVE}.sha256)
+echo "The release archive ${ARCHIVE} and its checksum file were generated in ${HOME}/ppcli_static"
@@ -0,0 +1,41 @@
+# https://github.com/emmanueltouzery/projectpad2
+# shell integration for ppcli: control-space to run
+# requires bash 4.4 or newer (for mapfile -d)
+
+# see integration.zsh for the rationale of the shell integration mode.
+ppcli-run() {
+    local pieces
+    # split by NUL, bash variables can't contain NUL characters
+    mapfile -d '' -t pieces < <(ppcli --shell-integration)
+    case "${pieces[0]}" in
+        R) # R == run
+            local cur_folder cmd folder
+            cur_folder=$(pwd)
+            if [[ -n ${pieces[2]} ]]; then
+                # the folder may contain spaces
+                printf -v folder %q "${pieces[2]}"
+                cmd="cd $folder && ${pieces[1]}"
+            else
+                cmd="${pieces[1]}"
+            fi
+            echo -e "\e[3m$cmd\e[0m" # print with italics because it wasn't really _typed_
+            history -s "$cmd"
+            # need the </dev/tty and the stty so that ssh shells work,
+            # and the printf to disable bracketed paste, same as for zsh
+            eval "stty sane; printf '\e[?2004l'; $cmd" </dev/tty
+            cd "$cur_folder" || return
+            ;;
+        P) # P == print to the prompt
+            READLINE_LINE="${pieces[1]}"
+            READLINE_POINT=${#READLINE_LINE}
+            ;;
+        C) # C == Copied to the clipboard, ppcli did it already
+            ;;
+    esac
+    # ppcli ends its output with a newline
+    local upgrade_url="${pieces[3]%$'\n'}"
+    if [[ -n $upgrade_url ]]; then
+        echo -e "\n\nppcli has detected a new version is available.\nIt's recommended to upgrade by running:\n ppcli --upgrade\n new version URL: $upgrade_url"
+    fi
+}
+bind -x '"\C- ": ppcli-run'
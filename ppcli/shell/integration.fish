@@ -0,0 +1,33 @@
+# https://github.com/emmanueltouzery/projectpad2
+# shell integration for ppcli: control-space to run
+
+# see integration.zsh for the rationale of the shell integration mode.
+function ppcli-run
+    # split by NUL
+    set -l pieces (ppcli --shell-integration | string split0)
+    switch "$pieces[1]"
+        case R # R == run
+            set -l cmd $pieces[2]
+            if test -n "$pieces[3]"
+                # go back to the current folder after running the command
+                set cmd "if pushd "(string escape -- $pieces[3])"; $pieces[2]; popd; end"
+            end
+            # executing from the command-line adds the command to the history,
+            # and runs it with the terminal in the right state for ssh shells
+            commandline -r -- $cmd
+            commandline -f execute
+        case P # P == print to the prompt
+            commandline -r -- $pieces[2]
+            commandline -f repaint
+        case C # C == Copied to the clipboard, ppcli did it already
+            commandline -f repaint
+    end
+    # ppcli ends its output with a newline
+    set -l upgrade_url (string trim -- "$pieces[4]")
+    if test -n "$upgrade_url"
+        echo -e "\n\nppcli has detected a new version is available.\nIt's recommended to upgrade by running:\n ppcli --upgrade\n new version URL: $upgrade_url"
+        commandline -f repaint
+    end
+end
+bind -k nul ppcli-run
+bind -M insert -k nul ppcli-run
@@ -1,6 +1,6 @@
 use crate::database::ActionType;
-use crate::database::{ItemOfInterest, ItemType, LinkedItemId};
-use projectpadsql::models::{InterestType, RunOn, ServerAccessType};
+use crate::database::{ItemOfInterest, ItemType, JumpHost, LinkedItemId};
+use projectpadsql::models::{InterestType, RunOn, ServerAccessType, ServerType};
 use std::borrow::Cow;
 
 enum SshCommandType {
@@ -25,26 +25,78 @@ fn try_prepare_ssh_command(
         [addr] => Some([addr, "22"]),
         _ => None,
     } {
-        let username = &item.server_info.as_ref().unwrap().server_username;
+        let server_info = item.server_info.as_ref().unwrap();
+        let username = &server_info.server_username;
         let user_param = if username.is_empty() {
             Cow::Borrowed("")
         } else {
             Cow::Owned(format!("{}@", username))
         };
+        let jump_param = ssh_jump_params(&server_info.ssh_jump_hosts);
+        let key_param = match &server_info.ssh_auth_key {
+            Some(key) => format!(
+                "-i {} {}",
+                shell_words::quote(&key.path.to_string_lossy()),
+                jump_param
+            ),
+            None => jump_param,
+        };
         Some(match (ssh_command_type, port) {
             // don't pass in the -p/-P parameter if we're using the default port
             // I sometimes use alt-enter to edit a ssh command into a scp command
             // and the -p/-P difference gets in the way...
-            (SshCommandType::Ssh, "22") => format!("ssh {}{}", user_param, addr),
-            (SshCommandType::Scp, "22") => format!("scp {}{}", user_param, addr),
-            (SshCommandType::Ssh, _) => format!("ssh -p {} {}{}", port, user_param, addr),
-            (SshCommandType::Scp, _) => format!("scp -P {} {}{}", port, user_param, addr),
+            (SshCommandType::Ssh, "22") => format!("ssh {}{}{}", key_param, user_param, addr),
+            (SshCommandType::Scp, "22") => format!("scp {}{}{}", key_param, user_param, addr),
+            (SshCommandType::Ssh, _) => {
+                format!("ssh {}-p {} {}{}", key_param, port, user_param, addr)
+            }
+            (SshCommandType::Scp, _) => {
+                format!("scp {}-P {} {}{}", key_param, port, user_param, addr)
+            }
         })
     } else {
         None
     }
 }
 
+/// -J is supported by both ssh and scp, and handles multiple hops, but
+/// it doesn't pass our stored keys to the jump hosts. When a jump host
+/// has a stored key, we go through it with a ProxyCommand instead.
+fn ssh_jump_params(jump_hosts: &[JumpHost]) -> String {
+    match jump_hosts.split_last() {
+        None => "".to_string(),
+        Some(_) if jump_hosts.iter().all(|h| h.ssh_auth_key.is_none()) => format!(
+            "-J {} ",
+            jump_hosts
+                .iter()
+                .map(|h| h.spec.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Some((last, previous)) => {
+            let (host, port_param) = match last.spec.split_once(':') {
+                Some((host, port)) => (host, format!("-p {} ", port)),
+                None => (last.spec.as_str(), "".to_string()),
+            };
+            let key_param = last
+                .ssh_auth_key
+                .as_ref()
+                .map(|k| format!("-i {} ", shell_words::quote(&k.path.to_string_lossy())))
+                .unwrap_or_default();
+            // ssh expands the % sequences of the ProxyCommand: escape
+            // the ones of the ProxyCommands of the previous hops
+            let proxy_command = format!(
+                "ssh {}{}{}-W %h:%p {}",
+                key_param,
+                ssh_jump_params(previous).replace('%', "%%"),
+                port_param,
+                host
+            );
+            format!("-o ProxyCommand={} ", shell_words::quote(&proxy_command))
+        }
+    }
+}
+
 fn get_value_server_ssh(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     if let Some(ssh_command) = try_prepare_ssh_command(item, SshCommandType::Ssh) {
         Cow::Owned(ssh_command)
@@ -53,9 +105,13 @@ fn get_value_server_ssh(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     }
 }
 
-fn is_ssh_access(item: &ItemOfInterest) -> bool {
+pub fn is_ssh_access(item: &ItemOfInterest) -> bool {
     match &item.server_info {
-        Some(srv) => srv.server_access_type == ServerAccessType::SrvAccessSsh,
+        Some(srv) => [
+            ServerAccessType::SrvAccessSsh,
+            ServerAccessType::SrvAccessSshTunnel,
+        ]
+        .contains(&srv.server_access_type),
         None => false,
     }
 }
@@ -110,15 +166,14 @@ fn get_value_less_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     get_value_action_file(item, ForcePseudoTTY::Yes, Cow::Borrowed("less"))
 }
 
+/// the user picks the folder when running the action, the download
+/// folder by default (see placeholders.rs)
+const DESTINATION_FOLDER: &str = "{{folder:destination folder}}";
+
 fn get_value_fetch_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     if let Some(scp_command) = try_prepare_ssh_command(item, SshCommandType::Scp) {
         let filename = item.poi_info.as_ref().unwrap().path.to_str().unwrap();
-        let base_command = format!(
-            "{}:{} {}",
-            scp_command,
-            filename,
-            dirs::download_dir().unwrap().to_str().unwrap()
-        );
+        let base_command = format!("{}:{} {}", scp_command, filename, DESTINATION_FOLDER);
         Cow::Owned(if filename.contains('`') {
             // support shell expansion with ` in filenames, so that you can for instance
             // have as a file name /opt/app/myapp/logs/myfile.`date "+%Y-%m-%d"`.log
@@ -132,6 +187,113 @@ fn get_value_fetch_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     }
 }
 
+pub fn is_in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+const BACKUP_LISTING_LENGTH: usize = 20;
+
+/// the newest archives first, with their size and date
+fn backup_listing_command(item: &ItemOfInterest) -> String {
+    format!(
+        "ls -lth {} | head -n {}",
+        shell_words::quote(&item.poi_info.as_ref().unwrap().path.to_string_lossy()),
+        // ls -l starts with the total size
+        BACKUP_LISTING_LENGTH + 1
+    )
+}
+
+fn get_value_list_backups(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match get_ssh_command(item, &backup_listing_command(item)) {
+        Some(command) => Cow::Owned(command),
+        None => Cow::Borrowed(&item.item_text),
+    }
+}
+
+/// the command showing the contents of the preview pane, for items
+/// where the database doesn't have enough info. skim runs it in the
+/// background: it must not prompt.
+pub fn get_preview_command(item: &ItemOfInterest) -> Option<String> {
+    if item.item_type != ItemType::InterestItemType(InterestType::PoiBackupArchive) {
+        return None;
+    }
+    // we don't write the stored ssh keys to disk for previews
+    if crate::authkeys::item_auth_keys(item).next().is_some() {
+        return None;
+    }
+    try_prepare_ssh_command_with_options(item, "-o BatchMode=yes -o ConnectTimeout=5").map(
+        |ssh_command| {
+            format!(
+                "{} {}",
+                ssh_command,
+                shell_words::quote(&backup_listing_command(item))
+            )
+        },
+    )
+}
+
+/// backup archives can be large: use rsync if it's installed, to get
+/// progress info and to resume interrupted transfers. If the path is a
+/// folder, we fetch the newest archive in it. We ask the server for its
+/// name first, then fetch it to the folder given as parameter to sh.
+fn get_value_fetch_backup(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    let path =
+        shell_words::quote(&item.poi_info.as_ref().unwrap().path.to_string_lossy()).to_string();
+    let find_latest = format!(
+        "if [ -d {0} ]; then echo {0}/\"$(ls -t {0} | head -n 1)\"; else echo {0}; fi",
+        path
+    );
+    let fetch = match (
+        try_prepare_ssh_command(item, SshCommandType::Ssh),
+        try_prepare_ssh_command(item, SshCommandType::Scp),
+    ) {
+        (Some(ssh_command), _) if is_in_path("rsync") => {
+            // rsync wants the ssh options separately from the host.
+            // -s: the remote shell must not split the path
+            let (ssh_options, host) = ssh_command.rsplit_once(' ').unwrap();
+            format!(
+                "rsync -a -s --partial --progress -e {} {}:\"$latest\" \"$1\"",
+                shell_words::quote(ssh_options),
+                host
+            )
+        }
+        (_, Some(scp_command)) => format!(
+            // the newest entry may be a folder. The remote shell (or the
+            // sftp glob, for recent scp) would split the path: escape it
+            "scp -r {}:\"$(printf %s \"$latest\" | sed 's/[^[:alnum:]/._-]/\\\\&/g')\" \"$1\"",
+            scp_command.trim_start_matches("scp ")
+        ),
+        _ => return Cow::Borrowed(&item.item_text),
+    };
+    match get_ssh_command(item, &find_latest) {
+        Some(ssh_find_latest) => Cow::Owned(format!(
+            "sh -c {} sh {}",
+            shell_words::quote(&format!("latest=$({}) && {}", ssh_find_latest, fetch)),
+            DESTINATION_FOLDER
+        )),
+        None => Cow::Borrowed(&item.item_text),
+    }
+}
+
+/// the ssh command to run a command on the server, without a terminal
+pub fn get_ssh_command(item: &ItemOfInterest, command: &str) -> Option<String> {
+    try_prepare_ssh_command(item, SshCommandType::Ssh)
+        .map(|ssh_command| format!("{} {}", ssh_command, shell_words::quote(command)))
+}
+
+fn get_value_push_file(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        LinkedItemId::ServerPoi(id) => Cow::Owned(format!(
+            "{} push --poi {} --folder {{{{folder:folder of the edited copy}}}}",
+            ppcli_command(),
+            id
+        )),
+        _ => panic!("unexpected item for push: {:?}", item.linked_item),
+    }
+}
+
 // https://serverfault.com/a/738797/176574
 fn get_value_ssh_cd_in_folder(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     if let Some(ssh_command) = try_prepare_ssh_command(item, SshCommandType::Ssh) {
@@ -167,6 +329,285 @@ fn get_value_ssh_run_on_ssh(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     Cow::Borrowed(&item.item_text)
 }
 
+/// the ssh command to run a command on the server without a terminal,
+/// in batch mode: when running on several servers in parallel we
+/// can't prompt for passwords.
+pub fn get_ssh_batch_command(item: &ItemOfInterest, command: &str) -> Option<String> {
+    try_prepare_ssh_command_with_options(item, "-o BatchMode=yes")
+        .map(|ssh_command| format!("{} {}", ssh_command, shell_words::quote(command)))
+}
+
+/// the ssh command to the server, with extra options before the
+/// ones to reach the server
+fn try_prepare_ssh_command_with_options(item: &ItemOfInterest, options: &str) -> Option<String> {
+    try_prepare_ssh_command(item, SshCommandType::Ssh)
+        .map(|ssh_command| format!("ssh {} {}", options, ssh_command.trim_start_matches("ssh ")))
+}
+
+pub fn ppcli_command() -> String {
+    std::env::current_exe()
+        .map(|p| shell_words::quote(&p.to_string_lossy()).to_string())
+        .unwrap_or_else(|_| "ppcli".to_string())
+}
+
+fn get_value_fan_out_poi(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        LinkedItemId::ServerPoi(id) => {
+            Cow::Owned(format!("{} fan-out --poi {}", ppcli_command(), id))
+        }
+        _ => panic!("unexpected item for fan-out: {:?}", item.linked_item),
+    }
+}
+
+fn get_value_fan_out_server(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        // without a command, fan-out asks for it
+        LinkedItemId::Server(id) => {
+            Cow::Owned(format!("{} fan-out --server {}", ppcli_command(), id))
+        }
+        _ => panic!("unexpected item for fan-out: {:?}", item.linked_item),
+    }
+}
+
+/// fan-out checks --allow-prod itself, it must get the flag we got
+pub fn forward_allow_prod<'a>(
+    action: &Action,
+    command: Cow<'a, str>,
+    allow_prod: bool,
+) -> Cow<'a, str> {
+    if allow_prod && action.desc == ActionType::RunOnGroup {
+        Cow::Owned(format!("{} --allow-prod", command))
+    } else {
+        command
+    }
+}
+
+fn get_value_rdp(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        // ppcli gives the password to the RDP client, so that it's
+        // not on the command-line
+        LinkedItemId::Server(id) => Cow::Owned(format!("{} rdp --server {}", ppcli_command(), id)),
+        _ => panic!("unexpected item for rdp: {:?}", item.linked_item),
+    }
+}
+
+fn get_value_view_note(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        LinkedItemId::ProjectNote(id) => {
+            Cow::Owned(format!("{} note --project-note {}", ppcli_command(), id))
+        }
+        LinkedItemId::ServerNote(id) => {
+            Cow::Owned(format!("{} note --server-note {}", ppcli_command(), id))
+        }
+        _ => panic!("unexpected item for a note: {:?}", item.linked_item),
+    }
+}
+
+/// the code blocks of server notes run on the server
+fn get_value_ssh_run_snippet(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match try_prepare_ssh_command(item, SshCommandType::Ssh) {
+        Some(ssh_command) => Cow::Owned(format!(
+            "{} -t {}",
+            ssh_command,
+            shell_words::quote(&item.item_text)
+        )),
+        None => Cow::Borrowed(&item.item_text),
+    }
+}
+
+/// the password of a server, database or extra user account
+fn get_value_password(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        LinkedItemId::ServerDatabase(_) => {
+            Cow::Borrowed(&item.db_info.as_ref().unwrap().db_password)
+        }
+        LinkedItemId::Server(_) | LinkedItemId::ServerExtraUserAccount(_) => {
+            Cow::Borrowed(&item.server_info.as_ref().unwrap().server_password)
+        }
+        _ => Cow::Borrowed(""),
+    }
+}
+
+fn has_server_group(item: &ItemOfInterest) -> bool {
+    item.server_info
+        .as_ref()
+        .and_then(|s| s.server_group.as_ref())
+        .is_some()
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum DbClient {
+    Postgres,
+    Mysql,
+}
+
+/// projectpad doesn't store the database engine, so guess it from
+/// the database & server descriptions and notes. If there's no hint,
+/// offer all the clients we know about.
+fn guess_db_clients(item: &ItemOfInterest) -> Vec<DbClient> {
+    let hint = [
+        item.db_info.as_ref().map(|d| d.db_text.as_str()),
+        item.poi_desc.as_deref(),
+        item.server_info.as_ref().map(|s| s.server_desc.as_str()),
+    ]
+    .iter()
+    .flatten()
+    .map(|s| s.to_lowercase())
+    .collect::<Vec<_>>()
+    .join(" ");
+    let mut clients = vec![];
+    if ["postgres", "psql", "pgsql"]
+        .iter()
+        .any(|h| hint.contains(h))
+    {
+        clients.push(DbClient::Postgres);
+    }
+    if ["mysql", "mariadb"].iter().any(|h| hint.contains(h)) {
+        clients.push(DbClient::Mysql);
+    }
+    if clients.is_empty() {
+        vec![DbClient::Postgres, DbClient::Mysql]
+    } else {
+        clients
+    }
+}
+
+fn get_value_db_client(item: &ItemOfInterest, client: DbClient) -> std::borrow::Cow<'_, str> {
+    let db_info = item.db_info.as_ref().unwrap();
+    if let Some(ssh_command) = try_prepare_ssh_command(item, SshCommandType::Ssh) {
+        // don't put the password on the command-line, the client will prompt
+        // for it. It can be copied to the clipboard through "copy pass".
+        let (program, user_flag) = match client {
+            DbClient::Postgres => ("psql", "-U"),
+            DbClient::Mysql => ("mysql", "-u"),
+        };
+        let mut args = vec![program];
+        if !db_info.db_username.is_empty() {
+            args.extend(&[user_flag, &db_info.db_username]);
+        }
+        if client == DbClient::Mysql {
+            args.push("-p");
+        }
+        args.push(&db_info.db_name);
+        // quoted for the remote shell, then for the local one
+        let client_command = shell_words::join(args);
+        Cow::Owned(format!(
+            "{} -t {}",
+            ssh_command,
+            shell_words::quote(&client_command)
+        ))
+    } else {
+        Cow::Borrowed(&item.item_text)
+    }
+}
+
+impl DbClient {
+    fn default_port(self) -> u16 {
+        match self {
+            DbClient::Postgres => 5432,
+            DbClient::Mysql => 3306,
+        }
+    }
+
+    fn url_scheme(self) -> &'static str {
+        match self {
+            DbClient::Postgres => "postgresql",
+            DbClient::Mysql => "mysql",
+        }
+    }
+}
+
+/// the URL to give to database tools (DBeaver...) to connect through
+/// the forwarded port
+fn port_forward_connection_string(
+    item: &ItemOfInterest,
+    client: DbClient,
+    local_port: u16,
+) -> String {
+    match &item.db_info {
+        Some(db_info) if db_info.db_username.is_empty() => format!(
+            "{}://localhost:{}/{}",
+            client.url_scheme(),
+            local_port,
+            db_info.db_name
+        ),
+        Some(db_info) => format!(
+            "{}://{}@localhost:{}/{}",
+            client.url_scheme(),
+            db_info.db_username,
+            local_port,
+            db_info.db_name
+        ),
+        None => format!("{}://localhost:{}", client.url_scheme(), local_port),
+    }
+}
+
+/// the default port of the database guessed for the item, postgres if
+/// there's no hint
+pub fn db_default_port(item: &ItemOfInterest) -> u16 {
+    guess_db_clients(item)[0].default_port()
+}
+
+/// the connection string to give to database tools, and the ssh command
+/// forwarding the local port to the database port on the server, through
+/// its jump hosts if any. ssh then runs until ctrl-c.
+pub fn forward_port_command(item: &ItemOfInterest, local_port: u16) -> Option<(String, String)> {
+    let client = guess_db_clients(item)[0];
+    let forward = format!("-N -L {}:localhost:{}", local_port, client.default_port());
+    try_prepare_ssh_command_with_options(item, &forward).map(|ssh_command| {
+        (
+            port_forward_connection_string(item, client, local_port),
+            ssh_command,
+        )
+    })
+}
+
+/// the local port is picked when the action runs, see forward.rs
+fn get_value_forward_port(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    match item.linked_item {
+        LinkedItemId::ServerDatabase(id) => {
+            Cow::Owned(format!("{} forward --database {}", ppcli_command(), id))
+        }
+        LinkedItemId::Server(id) => {
+            Cow::Owned(format!("{} forward --server {}", ppcli_command(), id))
+        }
+        _ => panic!(
+            "unexpected item for port forwarding: {:?}",
+            item.linked_item
+        ),
+    }
+}
+
+fn get_value_db_psql(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    get_value_db_client(item, DbClient::Postgres)
+}
+
+fn get_value_db_mysql(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    get_value_db_client(item, DbClient::Mysql)
+}
+
+fn open_url_command(url: &str) -> String {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    // urls frequently contain & or ?, which the shell would interpret
+    format!("{} {}", opener, shell_words::quote(url))
+}
+
+fn get_value_open_url(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    Cow::Owned(open_url_command(&item.item_text))
+}
+
+fn get_value_website_username(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    Cow::Borrowed(&item.website_info.as_ref().unwrap().username)
+}
+
+fn get_value_website_password(item: &ItemOfInterest) -> std::borrow::Cow<'_, str> {
+    Cow::Borrowed(&item.website_info.as_ref().unwrap().password)
+}
+
 #[derive(PartialEq)]
 pub enum AllowedAction {
     Run,
@@ -182,6 +623,21 @@ pub struct Action {
 }
 
 impl Action {
+    /// for actions which only make sense to copy to the clipboard,
+    /// for instance credentials
+    fn new_copy_only(
+        desc: ActionType,
+        get_string: fn(&ItemOfInterest) -> Cow<str>,
+        item: ItemOfInterest,
+    ) -> Action {
+        Action {
+            item,
+            desc,
+            get_string,
+            allowed_actions: vec![AllowedAction::CopyToClipboard],
+        }
+    }
+
     fn new(
         desc: ActionType,
         get_string: fn(&ItemOfInterest) -> Cow<str>,
@@ -201,6 +657,17 @@ impl Action {
 }
 
 pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
+    // whatever else we can do with them, the items with credentials
+    // offer to copy the password
+    let password_action = Some(&item)
+        .filter(|i| !get_value_password(i).is_empty())
+        .map(|i| Action::new_copy_only(ActionType::CopyPassword, get_value_password, i.clone()));
+    let mut actions = get_item_actions(item);
+    actions.extend(password_action);
+    actions
+}
+
+fn get_item_actions(item: ItemOfInterest) -> Vec<Action> {
     match &item {
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiLogFile)
             && is_ssh_access(i) =>
@@ -231,13 +698,37 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
                 item,
             }]
         }
-        i if matches!(i.linked_item, LinkedItemId::Server(_)) && is_ssh_access(i) => {
+        i if matches!(i.linked_item, LinkedItemId::ServerExtraUserAccount(_))
+            && is_ssh_access(i) =>
+        {
             vec![Action::new(
-                ActionType::SshShell,
+                ActionType::SshAsUser,
                 get_value_server_ssh,
                 item,
             )]
         }
+        i if matches!(i.linked_item, LinkedItemId::Server(_)) && is_ssh_access(i) => {
+            let mut actions = vec![Action::new(
+                ActionType::SshShell,
+                get_value_server_ssh,
+                item.clone(),
+            )];
+            if i.item_type == ItemType::ServerItemType(ServerType::SrvDatabase) {
+                actions.push(Action::new(
+                    ActionType::ForwardPort,
+                    get_value_forward_port,
+                    item.clone(),
+                ));
+            }
+            if has_server_group(i) {
+                actions.push(Action::new(
+                    ActionType::RunOnGroup,
+                    get_value_fan_out_server,
+                    item,
+                ));
+            }
+            actions
+        }
         i if [
             ItemType::InterestItemType(InterestType::PoiCommandToRun),
             ItemType::InterestItemType(InterestType::PoiCommandTerminal),
@@ -254,11 +745,22 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
         .contains(&i.item_type)
             && is_ssh_access(i) =>
         {
-            vec![Action::new(
+            let mut actions = vec![Action::new(
                 ActionType::RunCmd,
                 get_value_ssh_run_on_ssh,
-                item,
-            )]
+                item.clone(),
+            )];
+            if i.item_type == ItemType::InterestItemType(InterestType::PoiCommandToRun)
+                && i.run_on == Some(RunOn::RunOnServer)
+                && has_server_group(i)
+            {
+                actions.push(Action::new(
+                    ActionType::RunOnGroup,
+                    get_value_fan_out_poi,
+                    item,
+                ));
+            }
+            actions
         }
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiConfigFile)
             && is_ssh_access(i) =>
@@ -266,9 +768,316 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
             vec![
                 Action::new(ActionType::EditCfg, get_value_edit_file, item.clone()),
                 Action::new(ActionType::LessCfg, get_value_less_file, item.clone()),
-                Action::new(ActionType::FetchCfg, get_value_fetch_file, item),
+                Action::new(ActionType::FetchCfg, get_value_fetch_file, item.clone()),
+                Action::new(ActionType::PushCfg, get_value_push_file, item),
+            ]
+        }
+        i if i.item_type == ItemType::InterestItemType(InterestType::PoiBackupArchive)
+            && is_ssh_access(i) =>
+        {
+            vec![
+                Action::new(
+                    ActionType::ListBackups,
+                    get_value_list_backups,
+                    item.clone(),
+                ),
+                Action::new(
+                    ActionType::FetchBackup,
+                    get_value_fetch_backup,
+                    item.clone(),
+                ),
+                Action::new(ActionType::SshFolder, get_value_ssh_cd_in_folder, item),
             ]
         }
+        i if i.item_type == ItemType::Database => {
+            let mut actions = vec![];
+            if is_ssh_access(i) {
+                for client in guess_db_clients(i) {
+                    actions.push(match client {
+                        DbClient::Postgres => {
+                            Action::new(ActionType::PsqlDb, get_value_db_psql, item.clone())
+                        }
+                        DbClient::Mysql => {
+                            Action::new(ActionType::MysqlDb, get_value_db_mysql, item.clone())
+                        }
+                    });
+                }
+                actions.push(Action::new(
+                    ActionType::ForwardPort,
+                    get_value_forward_port,
+                    item,
+                ));
+            }
+            actions
+        }
+        i if i.item_type == ItemType::Website => {
+            let website_info = i.website_info.as_ref().unwrap();
+            let has_username = !website_info.username.is_empty();
+            let has_password = !website_info.password.is_empty();
+            let mut actions = vec![Action::new(
+                ActionType::OpenUrl,
+                get_value_open_url,
+                item.clone(),
+            )];
+            if has_username {
+                actions.push(Action::new_copy_only(
+                    ActionType::CopyUsername,
+                    get_value_website_username,
+                    item.clone(),
+                ));
+            }
+            if has_password {
+                actions.push(Action::new_copy_only(
+                    ActionType::CopyPassword,
+                    get_value_website_password,
+                    item,
+                ));
+            }
+            actions
+        }
+        i if matches!(i.linked_item, LinkedItemId::Server(_))
+            && i.server_info.as_ref().map(|s| s.server_access_type)
+                == Some(ServerAccessType::SrvAccessRdp) =>
+        {
+            vec![Action::new(ActionType::Rdp, get_value_rdp, item)]
+        }
+        i if matches!(i.linked_item, LinkedItemId::Server(_))
+            && i.server_info.as_ref().map(|s| s.server_access_type)
+                == Some(ServerAccessType::SrvAccessWww) =>
+        {
+            vec![Action::new(ActionType::OpenUrl, get_value_open_url, item)]
+        }
+        i if i.item_type == ItemType::Note => {
+            vec![Action::new(ActionType::ViewNote, get_value_view_note, item)]
+        }
+        i if i.item_type == ItemType::Snippet && is_ssh_access(i) => vec![Action::new(
+            ActionType::RunCmd,
+            get_value_ssh_run_snippet,
+            item,
+        )],
+        i if i.item_type == ItemType::Snippet && i.server_info.is_none() => {
+            vec![Action::new(ActionType::RunCmd, get_value_text, item)]
+        }
+        i if i.item_type == ItemType::Snippet => {
+            vec![Action {
+                desc: ActionType::CopySnippet,
+                get_string: get_value_text,
+                // windows or web servers: we can't run the snippet for the user
+                allowed_actions: vec![AllowedAction::CopyToClipboard, AllowedAction::CopyToPrompt],
+                item,
+            }]
+        }
         _ => Vec::new(),
     }
 }
+
+#[cfg(test)]
+fn test_server_item(server_ip: &str, ssh_jump_hosts: Vec<String>) -> ItemOfInterest {
+    ItemOfInterest {
+        linked_item: LinkedItemId::Server(1),
+        project_name: "prj".to_string(),
+        env: None,
+        item_type: ItemType::ServerItemType(projectpadsql::models::ServerType::SrvApplication),
+        poi_desc: None,
+        item_text: server_ip.to_string(),
+        server_info: Some(crate::database::ServerInfo {
+            server_id: 1,
+            server_desc: "srv".to_string(),
+            server_username: "user".to_string(),
+            server_ip: server_ip.to_string(),
+            server_access_type: ServerAccessType::SrvAccessSshTunnel,
+            ssh_jump_hosts: ssh_jump_hosts
+                .into_iter()
+                .map(|spec| JumpHost {
+                    spec,
+                    ssh_auth_key: None,
+                })
+                .collect(),
+            ssh_auth_key: None,
+            server_group: None,
+            server_password: "".to_string(),
+        }),
+        poi_info: None,
+        db_info: None,
+        website_info: None,
+        run_on: None,
+    }
+}
+
+#[test]
+fn ssh_command_through_jump_hosts() {
+    let item = test_server_item(
+        "10.0.0.3",
+        vec!["admin@bastion".to_string(), "10.0.0.2:2222".to_string()],
+    );
+    assert_eq!(
+        Some("ssh -J admin@bastion,10.0.0.2:2222 user@10.0.0.3".to_string()),
+        try_prepare_ssh_command(&item, SshCommandType::Ssh)
+    );
+    let item = test_server_item("10.0.0.3:2022", vec!["admin@bastion".to_string()]);
+    assert_eq!(
+        Some("scp -J admin@bastion -P 2022 user@10.0.0.3".to_string()),
+        try_prepare_ssh_command(&item, SshCommandType::Scp)
+    );
+}
+
+#[test]
+fn ssh_command_with_auth_key() {
+    let mut item = test_server_item("10.0.0.3:2022", vec!["admin@bastion".to_string()]);
+    item.server_info.as_mut().unwrap().ssh_auth_key = Some(crate::database::SshAuthKey {
+        path: "/run/user/1000/ppcli/extra-user-2".into(),
+        contents: vec![],
+    });
+    assert_eq!(
+        Some(
+            "ssh -i /run/user/1000/ppcli/extra-user-2 -J admin@bastion -p 2022 user@10.0.0.3"
+                .to_string()
+        ),
+        try_prepare_ssh_command(&item, SshCommandType::Ssh)
+    );
+}
+
+#[test]
+fn ssh_command_through_jump_host_with_auth_key() {
+    let mut item = test_server_item(
+        "10.0.0.3",
+        vec!["admin@bastion".to_string(), "10.0.0.2:2222".to_string()],
+    );
+    item.server_info.as_mut().unwrap().ssh_jump_hosts[1].ssh_auth_key =
+        Some(crate::database::SshAuthKey {
+            path: "/run/user/1000/ppcli/server-2".into(),
+            contents: vec![],
+        });
+    assert_eq!(
+        Some(
+            "ssh -o ProxyCommand='ssh -i /run/user/1000/ppcli/server-2 -J admin@bastion -p 2222 -W %h:%p 10.0.0.2' user@10.0.0.3"
+                .to_string()
+        ),
+        try_prepare_ssh_command(&item, SshCommandType::Ssh)
+    );
+    item.server_info.as_mut().unwrap().ssh_jump_hosts[0].ssh_auth_key =
+        Some(crate::database::SshAuthKey {
+            path: "/run/user/1000/ppcli/server-1".into(),
+            contents: vec![],
+        });
+    let expected_proxy = "ssh -i /run/user/1000/ppcli/server-2 -o ProxyCommand='ssh -i /run/user/1000/ppcli/server-1 -W %%h:%%p admin@bastion' -p 2222 -W %h:%p 10.0.0.2";
+    assert_eq!(
+        Some(format!(
+            "ssh -o ProxyCommand={} user@10.0.0.3",
+            shell_words::quote(expected_proxy)
+        )),
+        try_prepare_ssh_command(&item, SshCommandType::Ssh)
+    );
+}
+
+#[test]
+fn guess_db_clients_from_hints() {
+    let mut item = test_server_item("10.0.0.3", vec![]);
+    item.poi_desc = Some("Main MariaDB".to_string());
+    item.db_info = Some(crate::database::DbInfo {
+        db_name: "app".to_string(),
+        db_text: "".to_string(),
+        db_username: "appuser".to_string(),
+        db_password: "".to_string(),
+    });
+    assert_eq!(vec![DbClient::Mysql], guess_db_clients(&item));
+    assert_eq!(
+        "ssh user@10.0.0.3 -t 'mysql -u appuser -p app'",
+        get_value_db_mysql(&item)
+    );
+    item.poi_desc = Some("Main DB".to_string());
+    assert_eq!(
+        vec![DbClient::Postgres, DbClient::Mysql],
+        guess_db_clients(&item)
+    );
+}
+
+#[test]
+fn db_client_command_quotes_the_names() {
+    let mut item = test_server_item("10.0.0.3", vec![]);
+    item.db_info = Some(crate::database::DbInfo {
+        db_name: "app db".to_string(),
+        db_text: "".to_string(),
+        db_username: "app;user".to_string(),
+        db_password: "".to_string(),
+    });
+    let remote_command = |client| {
+        shell_words::split(&get_value_db_client(&item, client))
+            .unwrap()
+            .last()
+            .map(|c| shell_words::split(c).unwrap())
+            .unwrap()
+    };
+    assert_eq!(
+        vec!["psql", "-U", "app;user", "app db"],
+        remote_command(DbClient::Postgres)
+    );
+    assert_eq!(
+        vec!["mysql", "-u", "app;user", "-p", "app db"],
+        remote_command(DbClient::Mysql)
+    );
+}
+
+#[test]
+fn forward_port_through_jump_hosts() {
+    let mut item = test_server_item("10.0.0.3", vec!["admin@bastion".to_string()]);
+    item.db_info = Some(crate::database::DbInfo {
+        db_name: "app".to_string(),
+        db_text: "postgres 12".to_string(),
+        db_username: "appuser".to_string(),
+        db_password: "".to_string(),
+    });
+    assert_eq!(
+        "postgresql://appuser@localhost:5433/app",
+        port_forward_connection_string(&item, DbClient::Postgres, 5433)
+    );
+    assert_eq!(
+        Some((
+            "postgresql://appuser@localhost:5433/app".to_string(),
+            "ssh -N -L 5433:localhost:5432 -J admin@bastion user@10.0.0.3".to_string()
+        )),
+        forward_port_command(&item, 5433)
+    );
+}
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn open_url_quotes_the_url() {
+    assert_eq!(
+        "xdg-open 'https://uat.example.com/admin?tenant=1&lang=en'",
+        open_url_command("https://uat.example.com/admin?tenant=1&lang=en")
+    );
+}
+
+#[test]
+fn copy_password_for_servers_and_extra_users() {
+    let mut item = test_server_item("10.0.0.3", vec![]);
+    assert!(!get_value(item.clone())
+        .iter()
+        .any(|a| a.desc == ActionType::CopyPassword));
+    item.server_info.as_mut().unwrap().server_password = "secret".to_string();
+    item.linked_item = LinkedItemId::ServerExtraUserAccount(2);
+    let actions = get_value(item);
+    let copy_pass = actions
+        .iter()
+        .find(|a| a.desc == ActionType::CopyPassword)
+        .unwrap();
+    assert_eq!("secret", (copy_pass.get_string)(&copy_pass.item));
+    assert!(copy_pass.allowed_actions == [AllowedAction::CopyToClipboard]);
+    assert!(actions.iter().any(|a| a.desc == ActionType::SshAsUser));
+}
+
+#[test]
+fn fan_out_server_gets_allow_prod() {
+    let mut item = test_server_item("10.0.0.3", vec![]);
+    item.server_info.as_mut().unwrap().server_group = Some("web".to_string());
+    let actions = get_value(item);
+    let run_group = actions
+        .iter()
+        .find(|a| a.desc == ActionType::RunOnGroup)
+        .unwrap();
+    let command = forward_allow_prod(run_group, (run_group.get_string)(&run_group.item), true);
+    assert!(command.ends_with(" fan-out --server 1 --allow-prod"));
+    let command = forward_allow_prod(run_group, (run_group.get_string)(&run_group.item), false);
+    assert!(command.ends_with(" fan-out --server 1"));
+}
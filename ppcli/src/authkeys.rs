@@ -0,0 +1,148 @@
+// ssh can only read private keys from files, so the keys that are stored
+// in the database must be written to disk before we can use them.
+// We write them in the user runtime folder ($XDG_RUNTIME_DIR), which is
+// normally a tmpfs only readable by the user, and which is cleared when
+// the user logs out.
+// When ppcli runs the command itself, it removes the keys once the command
+// completes. When the command is handed to the shell (shell integration,
+// pasted or copied), the user may run it a bit later: a background ppcli
+// process removes the keys after a delay.
+use crate::database::{ItemOfInterest, SshAuthKey};
+use std::error::Error;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// the folder for our files which must not outlive the user session
+pub fn runtime_folder() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(mut path) => {
+            path.push("ppcli");
+            path
+        }
+        None => {
+            let mut path = std::env::temp_dir();
+            path.push(format!("ppcli-{}", unsafe { libc::getuid() }));
+            path
+        }
+    }
+}
+
+/// the path where the key will be written. The path is
+/// stable, so that we can display the commands before writing the key.
+pub fn auth_key_path(kind: &str, id: i32) -> PathBuf {
+    let mut path = runtime_folder();
+    path.push(format!("{}-{}", kind, id));
+    path
+}
+
+/// create the folder if needed, accessible only by the current user
+pub fn create_private_folder(folder: &Path) -> std::io::Result<()> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(folder)?;
+    // the folder may already exist, with other permissions
+    std::fs::set_permissions(folder, std::fs::Permissions::from_mode(0o700))
+}
+
+/// write the key to disk, readable only by the current user
+pub fn materialize_auth_key(auth_key: &SshAuthKey) -> std::io::Result<()> {
+    if let Some(folder) = auth_key.path.parent() {
+        create_private_folder(folder)?;
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&auth_key.path)?;
+    // the file may already exist, with other permissions
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(&auth_key.contents)?;
+    Ok(())
+}
+
+/// the keys of the server of the item, and of its jump hosts
+pub fn item_auth_keys(item: &ItemOfInterest) -> impl Iterator<Item = &SshAuthKey> {
+    item.server_info.iter().flat_map(|s| {
+        s.ssh_auth_key.iter().chain(
+            s.ssh_jump_hosts
+                .iter()
+                .filter_map(|h| h.ssh_auth_key.as_ref()),
+        )
+    })
+}
+
+pub fn materialize_item_auth_key(item: &ItemOfInterest) {
+    for auth_key in item_auth_keys(item) {
+        if let Err(e) = materialize_auth_key(auth_key) {
+            eprintln!("Error writing the ssh key to {:?}: {}", auth_key.path, e);
+        }
+    }
+}
+
+/// remove the key from disk once we know it's not needed anymore.
+/// That's only the case when we ran the command ourselves: when the
+/// command is pasted to the prompt or copied, the user may run it later.
+pub fn remove_item_auth_key(item: &ItemOfInterest) {
+    for auth_key in item_auth_keys(item) {
+        let _ = std::fs::remove_file(&auth_key.path);
+    }
+}
+
+/// for commands handed to the shell: remove the keys of these items
+/// after the delay
+pub fn schedule_auth_keys_removal<'a>(
+    items: impl Iterator<Item = &'a ItemOfInterest>,
+    lifetime_secs: u64,
+) {
+    let paths: Vec<_> = items
+        .flat_map(item_auth_keys)
+        .map(|k| k.path.to_string_lossy().to_string())
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    if let Err(e) = start_removal_agent(&paths.join("\n"), lifetime_secs) {
+        eprintln!("Error scheduling the removal of the ssh keys: {}", e);
+    }
+}
+
+fn start_removal_agent(paths: &str, lifetime_secs: u64) -> Result<(), Box<dyn Error>> {
+    let mut child = unsafe {
+        Command::new(std::env::current_exe()?)
+            .arg("--remove-auth-keys")
+            .arg(lifetime_secs.to_string())
+            .stdin(Stdio::piped())
+            // in shell integration mode, the shell waits until our stdout
+            // is closed: the agent must not inherit it
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            // don't get killed with the terminal foreground process group
+            .pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            })
+            .spawn()?
+    };
+    child.stdin.take().unwrap().write_all(paths.as_bytes())?;
+    Ok(())
+}
+
+/// the agent process: reads the paths of the keys from stdin, one per
+/// line, and removes them after the delay. ssh reads the keys when it
+/// connects, sessions opened in the meantime are not affected.
+pub fn run_removal_agent(lifetime_secs: u64) -> Result<(), Box<dyn Error>> {
+    let mut paths = String::new();
+    std::io::stdin().read_to_string(&mut paths)?;
+    std::thread::sleep(Duration::from_secs(lifetime_secs));
+    for path in paths.lines() {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
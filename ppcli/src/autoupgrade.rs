@@ -1,16 +1,23 @@
+// checking for new versions of ppcli, and upgrading. The releases are
+// listed through the github API. Next to each archive, the release has a
+// .sha256 file with the checksum of the archive (the sha256sum output),
+// which we verify before replacing the binary.
+// PPCLI_RELEASES_URL overrides the URL of the github API, for instance
+// to test against a local HTTP server.
+use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::env;
-use std::path::Path;
-use std::process::Command;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 type UResult<T> = Result<T, Box<dyn std::error::Error>>;
 
-fn has_requirements() -> bool {
-    is_in_path("curl")
-        && is_in_path("jq")
-        && is_in_path("grep")
-        && is_in_path("head")
-        && is_in_path("tar")
-}
+const RELEASES_URL: &str = "https://api.github.com/repos/emmanueltouzery/projectpad2/releases";
+const RELEASES_URL_ENV_VAR: &str = "PPCLI_RELEASES_URL";
+
+// the archive is a few megabytes, don't fill the memory if the server misbehaves
+const MAX_DOWNLOAD_BYTES: u64 = 200 * 1024 * 1024;
 
 pub fn apply_upgrade(download_url: &str) -> UResult<()> {
     println!(
@@ -24,22 +31,103 @@ pub fn apply_upgrade(download_url: &str) -> UResult<()> {
     if input_trimmed != "y" && input_trimmed != "Y" {
         return Ok(());
     }
-    let parent_str = ppcli_path()?;
+    let archive = download(download_url)?;
+    let checksum = String::from_utf8(download(&format!("{}.sha256", download_url))?)?;
+    verify_checksum(&archive, &checksum)?;
+    install_archive(&archive)?;
+    println!("ppcli was upgraded.");
+    Ok(())
+}
 
-    Command::new("sh")
-        .args(&[
-            "-c",
-            &("cd ".to_string() + &parent_str + " && curl -L " + download_url + " | tar -xz"),
-        ])
-        .spawn()?
-        .wait()?;
+/// for machines without internet access: the archive downloaded from
+/// the releases, with its .sha256 file in the same folder
+pub fn upgrade_from(archive_path: &Path) -> UResult<()> {
+    let archive = fs::read(archive_path)
+        .map_err(|e| format!("Error reading {}: {}", archive_path.display(), e))?;
+    let mut checksum_path = archive_path.as_os_str().to_owned();
+    checksum_path.push(".sha256");
+    let checksum_path = PathBuf::from(checksum_path);
+    let checksum = fs::read_to_string(&checksum_path).map_err(|e| {
+        format!(
+            "Error reading {}, download it with the archive: {}",
+            checksum_path.display(),
+            e
+        )
+    })?;
+    verify_checksum(&archive, &checksum)?;
+    install_archive(&archive)?;
+    println!("ppcli was upgraded.");
+    Ok(())
+}
+
+fn download(url: &str) -> UResult<Vec<u8>> {
+    let mut contents = vec![];
+    ureq::get(url)
+        .call()
+        .map_err(|e| format!("Error downloading {}: {}", url, e))?
+        .into_reader()
+        .take(MAX_DOWNLOAD_BYTES)
+        .read_to_end(&mut contents)?;
+    Ok(contents)
+}
 
+/// the checksum file is in the sha256sum format: the checksum, then the file name
+fn verify_checksum(contents: &[u8], checksum_file: &str) -> UResult<()> {
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .ok_or("The checksum file is empty")?;
+    let actual = Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(format!(
+            "The checksum of the download doesn't match: expected {}, got {}",
+            expected, actual
+        )
+        .into());
+    }
     Ok(())
 }
 
-fn ppcli_path() -> UResult<String> {
+fn install_archive(archive: &[u8]) -> UResult<()> {
+    let exe_path = ppcli_path()?;
+    let parent = exe_path
+        .parent()
+        .ok_or("can't get the parent folder of the ppcli install")?;
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    let mut binary = tar
+        .entries()?
+        .filter_map(Result::ok)
+        .find(|e| {
+            e.path()
+                .map(|p| p.file_name() == Some("ppcli".as_ref()))
+                .unwrap_or(false)
+        })
+        .ok_or("There is no ppcli binary in the archive")?;
+    // write the new binary next to the old one, then rename it over the
+    // old one: the rename is atomic, there is never a partial ppcli binary
+    let new_path = parent.join(format!(".ppcli-upgrade-{}", std::process::id()));
+    let res = write_binary(&mut binary, &new_path, &exe_path)
+        .and_then(|_| fs::rename(&new_path, &exe_path).map_err(|e| e.into()));
+    if res.is_err() {
+        let _ = fs::remove_file(&new_path);
+    }
+    res
+}
+
+fn write_binary(contents: &mut impl Read, path: &Path, previous_path: &Path) -> UResult<()> {
+    let mut file = File::create(path)?;
+    std::io::copy(contents, &mut file)?;
+    file.set_permissions(fs::metadata(previous_path)?.permissions())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn ppcli_path() -> UResult<PathBuf> {
     // https://stackoverflow.com/a/4025426/516188 linuxism...
-    let full_path = match std::fs::read_link("/proc/self/exe") {
+    match fs::read_link("/proc/self/exe") {
         Ok(p) => Ok(p),
         Err(_) => {
             // presumably, not linux... get argv[0] and assume it's the
@@ -47,63 +135,48 @@ fn ppcli_path() -> UResult<String> {
             let path = env::args().next().ok_or("can't get the current app path")?;
             // TODO the user did not necessarily launch through the full path
             // should go through 'which' if path doesn't exist on disk.
-            std::fs::canonicalize(&path)
+            Ok(fs::canonicalize(&path)?)
         }
-    }?;
-    let parent = full_path
-        .parent()
-        .ok_or("can't get the parent folder of the ppcli install")?;
-    let parent_str = parent
-        .to_str()
-        .ok_or("the ppcli parent folder is an invalid string")?;
-    Ok(parent_str.to_owned())
+    }
+}
+
+fn releases_url() -> String {
+    env::var(RELEASES_URL_ENV_VAR).unwrap_or_else(|_| RELEASES_URL.to_string())
 }
 
 fn get_latest_download_url() -> UResult<String> {
-    if !has_requirements() {
-        return Err("Auto-upgrade requires the following programs in the path: curl, jq, grep, head and tar.".into());
-    }
+    let releases = String::from_utf8(download(&releases_url())?)?;
+    find_download_url(
+        &releases,
+        &format!("{}_{}", env::consts::OS, env::consts::ARCH),
+    )
+    .ok_or_else(|| "can't find a URL of a newer version of ppcli".into())
+}
 
-    let download_bytes = Command::new("sh")
-        .args(&[
-            "-c",
-            &[
-                "curl -sSf https://api.github.com/repos/emmanueltouzery/projectpad2/releases \
-                               | jq '.[] | .assets | select(length > 0)' \
-                               | jq -r '.[] | .browser_download_url' \
-                               | grep cli \
-                               | grep ",
-                std::env::consts::OS,
-                "_",
-                std::env::consts::ARCH,
-                " | head -n 1",
-            ]
-            .join(""),
-        ])
-        .output()?
-        .stdout;
-    let download_url = std::str::from_utf8(&download_bytes)?.trim();
-    if !download_url.starts_with("https://") {
-        return Err("can't find a URL of a newer version of ppcli".into());
-    }
-    Ok(download_url.to_owned())
+/// the releases are listed newest first
+fn find_download_url(releases_json: &str, platform: &str) -> Option<String> {
+    // i don't want to pull serde_json only for that
+    Regex::new(r#""browser_download_url"\s*:\s*"([^"]+)""#)
+        .unwrap()
+        .captures_iter(releases_json)
+        .map(|c| c[1].to_string())
+        .find(|url| {
+            let fname = url.rsplit('/').next().unwrap_or("");
+            fname.contains("cli") && fname.contains(platform) && fname.ends_with(".tgz")
+        })
 }
 
 fn download_url_extract_version(download_url: &str) -> UResult<&str> {
     let fname = download_url
-        .rsplitn(2, '/')
+        .rsplit('/')
         .next()
         .ok_or_else(|| format!("failed parsing download URL: {}", download_url))?;
     fname
-        .splitn(3, '-')
+        .split('-')
         .nth(1)
         .ok_or_else(|| format!("failed parsing download URL: {}", download_url).into())
 }
 
-// i don't want to add too many dependencies to ppcli itself,
-// just because of the auto-upgrade...
-// i would need to depend on a http library (ok, i already have
-// openssl), and a tar.gz extracting library. Not that bad, but...
 pub fn try_upgrade() -> UResult<()> {
     let download_url = get_latest_download_url()?;
     apply_upgrade(&download_url)
@@ -116,29 +189,25 @@ pub fn is_upgrade_available() -> UResult<Option<String>> {
     Ok(Some(download_url).filter(|_| is_new_version))
 }
 
-// https://stackoverflow.com/a/37499032/516188
-fn is_in_path<P>(exe_name: P) -> bool
-where
-    P: AsRef<Path>,
-{
-    env::var_os("PATH")
-        .and_then(|paths| {
-            env::split_paths(&paths)
-                .filter_map(|dir| {
-                    let full_path = dir.join(&exe_name);
-                    if full_path.is_file() {
-                        Some(full_path)
-                    } else {
-                        None
-                    }
-                })
-                .next()
-        })
-        .is_some()
-}
-
 #[test]
 fn parses_version_from_url() {
     assert_eq!("2.1.0", download_url_extract_version(
         "https://github.com/emmanueltouzery/projectpad2/releases/download/v2.1.0/ppcli-2.1.0-linux_x86_64.tgz").unwrap());
 }
+
+#[test]
+fn finds_download_url_and_verifies_checksum() {
+    let releases = r#"[{"assets": [
+        {"name": "ppcli-2.2.0-linux_x86_64.tgz.sha256", "browser_download_url": "https://example.com/v2.2.0/ppcli-2.2.0-linux_x86_64.tgz.sha256"},
+        {"name": "ppcli-2.2.0-linux_aarch64.tgz", "browser_download_url": "https://example.com/v2.2.0/ppcli-2.2.0-linux_aarch64.tgz"},
+        {"name": "ppcli-2.2.0-linux_x86_64.tgz", "browser_download_url": "https://example.com/v2.2.0/ppcli-2.2.0-linux_x86_64.tgz"}
+    ]}]"#;
+    assert_eq!(
+        Some("https://example.com/v2.2.0/ppcli-2.2.0-linux_x86_64.tgz".to_string()),
+        find_download_url(releases, "linux_x86_64")
+    );
+    // echo -n hello | sha256sum
+    let checksum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  ppcli.tgz\n";
+    assert!(verify_checksum(b"hello", checksum).is_ok());
+    assert!(verify_checksum(b"hellO", checksum).is_err());
+}
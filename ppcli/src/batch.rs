@@ -0,0 +1,133 @@
+// running several actions at once, after selecting several rows in the
+// list with tab. In tmux, each command gets its own pane. Otherwise the
+// commands run in parallel, each output line prefixed with the name of
+// the item, which only makes sense for commands which don't need a
+// terminal: tailing logs, fetching files, running commands.
+// In both cases we build a single command line, so that the batch
+// is handled like any other action, including in shell integration mode.
+use crate::actions::{self, Action, AllowedAction};
+use crate::database::{ActionType, ItemType};
+use projectpadsql::models::InterestType;
+use std::path::PathBuf;
+
+pub struct BatchCommand {
+    pub command: String,
+    /// true if the command returns before the actions complete
+    pub detached: bool,
+}
+
+fn can_merge_output(action: &Action) -> bool {
+    match action.desc {
+        ActionType::TailLog | ActionType::FetchLog | ActionType::FetchCfg => true,
+        ActionType::RunCmd => {
+            action.item.item_type == ItemType::InterestItemType(InterestType::PoiCommandToRun)
+        }
+        _ => false,
+    }
+}
+
+fn item_name(action: &Action) -> String {
+    let item = &action.item;
+    let name = [
+        item.server_info.as_ref().map(|s| s.server_desc.as_str()),
+        item.poi_desc.as_deref(),
+    ]
+    .iter()
+    .flatten()
+    .filter(|s| !s.is_empty())
+    .copied()
+    .collect::<Vec<_>>()
+    .join(" ");
+    if name.is_empty() {
+        item.item_text.clone()
+    } else {
+        name
+    }
+}
+
+fn command_in_folder(command: &str, folder: &Option<PathBuf>) -> String {
+    match folder {
+        Some(folder) => format!(
+            "sh -c {}",
+            shell_words::quote(&format!(
+                "cd {} && {}",
+                shell_words::quote(&folder.to_string_lossy()),
+                command
+            ))
+        ),
+        None => command.to_string(),
+    }
+}
+
+/// the actions come with their command and the folder to run it in
+pub fn prepare(
+    actions: &[(&Action, String, Option<PathBuf>)],
+    in_tmux: bool,
+) -> Result<BatchCommand, String> {
+    if let Some((action, _, _)) = actions
+        .iter()
+        .find(|(a, _, _)| !a.allowed_actions.contains(&AllowedAction::Run))
+    {
+        return Err(format!(
+            "The action '{}' for '{}' can't be run",
+            action.desc,
+            item_name(action)
+        ));
+    }
+    let commands = actions
+        .iter()
+        .map(|(_, command, folder)| command_in_folder(command, folder));
+    if in_tmux {
+        // a new window, with one pane per command. Keep the panes open
+        // when the commands exit, to see their output: set the option
+        // before the first command runs, by replacing the shell which
+        // tmux starts in the new window.
+        let panes = commands
+            .enumerate()
+            .map(|(i, command)| {
+                format!(
+                    "{} {}",
+                    if i == 0 {
+                        "respawn-pane -k"
+                    } else {
+                        "split-window"
+                    },
+                    shell_words::quote(&command)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" \\; ");
+        return Ok(BatchCommand {
+            command: format!(
+                "tmux new-window \\; set-window-option remain-on-exit on \\; {} \\; select-layout tiled",
+                panes
+            ),
+            detached: true,
+        });
+    }
+    if let Some((action, _, _)) = actions.iter().find(|(a, _, _)| !can_merge_output(a)) {
+        return Err(format!(
+            "The action '{}' for '{}' needs a terminal, it can be run with other actions only in tmux",
+            action.desc,
+            item_name(action)
+        ));
+    }
+    let names = actions
+        .iter()
+        .map(|(a, _, _)| format!("--name {}", shell_words::quote(&item_name(a))))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let commands = commands
+        .map(|c| shell_words::quote(&c).to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(BatchCommand {
+        command: format!(
+            "{} parallel {} -- {}",
+            actions::ppcli_command(),
+            names,
+            commands
+        ),
+        detached: false,
+    })
+}
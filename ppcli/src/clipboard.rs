@@ -0,0 +1,208 @@
+// copying to the clipboard. There are libraries for that in rust, earlier
+// i was using clipboard-ext, but:
+// - there are issues with keeping the contents of the clipboard
+//   after the app exits (need to fork, stay alive..)
+// - must link to a series of X11 or wayland-related libraries,
+//   on linux. But I want a static build so that i can distribute
+//   a cross-distro binary.
+// due to that, rather leverage wl-copy and xsel. It seems xsel is a
+// better choice than xclip:
+// https://askubuntu.com/questions/705620/xclip-vs-xsel/898094#898094
+// Over ssh or in a container there is neither wayland nor X11, then we
+// use the OSC 52 escape sequence: the terminal itself sets the clipboard.
+//
+// Passwords are cleared from the clipboard after a delay, by a background
+// ppcli process, like the database password agent.
+use crate::actions;
+use crate::config::{CliConfig, ClipboardMethod};
+use std::env;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Backend {
+    Wayland,
+    X11,
+    Osc52,
+}
+
+fn backend(method: ClipboardMethod) -> Backend {
+    match method {
+        ClipboardMethod::Wayland => Backend::Wayland,
+        ClipboardMethod::X11 => Backend::X11,
+        ClipboardMethod::Osc52 => Backend::Osc52,
+        // detect wayland or X11 https://unix.stackexchange.com/a/559950/36566
+        ClipboardMethod::Auto
+            if env::var("WAYLAND_DISPLAY").is_ok() && actions::is_in_path("wl-copy") =>
+        {
+            Backend::Wayland
+        }
+        ClipboardMethod::Auto if env::var("DISPLAY").is_ok() && actions::is_in_path("xsel") => {
+            Backend::X11
+        }
+        ClipboardMethod::Auto => Backend::Osc52,
+    }
+}
+
+/// secrets are cleared from the clipboard after clipboard_clear_secs
+pub fn copy(text: &str, config: &CliConfig, secret: bool) {
+    let backend = backend(config.clipboard_method);
+    if let Err(e) = set_contents(backend, text) {
+        eprintln!("Error copying to the clipboard: {}", e);
+        return;
+    }
+    if secret && config.clipboard_clear_secs > 0 {
+        if let Err(e) = start_clear_agent(backend, text, config.clipboard_clear_secs) {
+            eprintln!("Error scheduling the clipboard clearing: {}", e);
+        }
+    }
+}
+
+fn set_contents(backend: Backend, text: &str) -> Result<(), Box<dyn Error>> {
+    match backend {
+        // through stdin, not to show the text in the process list
+        Backend::Wayland => run_with_input("wl-copy", &[], text),
+        Backend::X11 => run_with_input("xsel", &["--clipboard", "--input"], text),
+        Backend::Osc52 => write_osc52(&mut open_tty()?, text),
+    }
+}
+
+fn run_with_input(program: &str, args: &[&str], input: &str) -> Result<(), Box<dyn Error>> {
+    // wl-copy and xsel stay in the background to serve the clipboard:
+    // they must not keep our stdout, which the shell integration waits on
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to invoke {}: {}", program, e))?;
+    child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("Got error status from {}: {}", program, status).into());
+    }
+    Ok(())
+}
+
+fn read_output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+}
+
+fn open_tty() -> Result<File, Box<dyn Error>> {
+    OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| format!("Cannot open the terminal: {}", e).into())
+}
+
+fn write_osc52(out: &mut impl Write, text: &str) -> Result<(), Box<dyn Error>> {
+    out.write_all(osc52_sequence(text, env::var("TMUX").is_ok()).as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+fn osc52_sequence(text: &str, in_tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if in_tmux {
+        // tmux forwards sequences wrapped in that way to the terminal,
+        // with the escape characters doubled. tmux 3.3 and newer need
+        // allow-passthrough enabled for that.
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+// i don't want to pull a crate only for that
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(input.len() * 4 / 3 + 4);
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+fn start_clear_agent(backend: Backend, text: &str, clear_secs: u64) -> Result<(), Box<dyn Error>> {
+    // the agent has no controlling terminal, it writes the escape
+    // sequence to the terminal we give it
+    let stdout = match backend {
+        Backend::Osc52 => Stdio::from(open_tty()?),
+        _ => Stdio::null(),
+    };
+    let mut child = unsafe {
+        Command::new(env::current_exe()?)
+            .arg("--clear-clipboard")
+            .arg(clear_secs.to_string())
+            .stdin(Stdio::piped())
+            .stdout(stdout)
+            .stderr(Stdio::null())
+            // don't get killed with the terminal foreground process group
+            .pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            })
+            .spawn()?
+    };
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// the agent process: reads the copied text from stdin, and clears
+/// the clipboard after the delay, if it still contains that text.
+pub fn run_clear_agent(clear_secs: u64, config: &CliConfig) -> Result<(), Box<dyn Error>> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    std::thread::sleep(Duration::from_secs(clear_secs));
+    match backend(config.clipboard_method) {
+        Backend::Wayland => {
+            if read_output("wl-paste", &["--no-newline"]).as_deref() == Some(&text) {
+                run_with_input("wl-copy", &["--clear"], "")?;
+            }
+        }
+        Backend::X11 => {
+            if read_output("xsel", &["--clipboard", "--output"]).as_deref() == Some(&text) {
+                run_with_input("xsel", &["--clipboard", "--clear"], "")?;
+            }
+        }
+        // we can't read the clipboard through the terminal, overwrite
+        // it even if the user copied something else in the meantime
+        Backend::Osc52 => write_osc52(&mut std::io::stdout(), "")?,
+    }
+    Ok(())
+}
+
+#[test]
+fn osc52_sequence_encodes_and_wraps_for_tmux() {
+    assert_eq!("", base64(b""));
+    assert_eq!("cGFzcw==", base64(b"pass"));
+    assert_eq!("cGFzc3c=", base64(b"passw"));
+    assert_eq!("cGFzc3dk", base64(b"passwd"));
+    assert_eq!("\x1b]52;c;cGFzcw==\x07", osc52_sequence("pass", false));
+    assert_eq!(
+        "\x1bPtmux;\x1b\x1b]52;c;cGFzcw==\x07\x1b\\",
+        osc52_sequence("pass", true)
+    );
+}
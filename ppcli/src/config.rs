@@ -1,13 +1,365 @@
 // bits lifted from the skim project
 use crate::database::ActionType;
-use crate::database::{ExecutedAction, LinkedItemId};
+use crate::database::{ActionHistoryEntry, ExecutedAction, LinkedItemId};
+use crate::placeholders;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{BufReader, BufWriter, ErrorKind};
+use std::io::{BufReader, BufWriter};
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::{Duration, SystemTime};
+use strum_macros::EnumString;
+
+/// how to paste a command to the shell prompt (when not using the
+/// shell integration).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString)]
+pub enum PasteMethod {
+    /// try the TIOCSTI ioctl, then the terminal multiplexer or emulator
+    /// we're running in, and finally print the command
+    #[strum(serialize = "auto")]
+    Auto,
+    #[strum(serialize = "tiocsti")]
+    Tiocsti,
+    #[strum(serialize = "tmux")]
+    Tmux,
+    #[strum(serialize = "kitty")]
+    Kitty,
+    #[strum(serialize = "wezterm")]
+    Wezterm,
+    #[strum(serialize = "print")]
+    Print,
+}
+
+/// how to copy to the clipboard
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString)]
+pub enum ClipboardMethod {
+    /// wayland or X11 if available, otherwise the OSC 52 escape sequence
+    #[strum(serialize = "auto")]
+    Auto,
+    /// wl-copy
+    #[strum(serialize = "wayland")]
+    Wayland,
+    /// xsel
+    #[strum(serialize = "x11")]
+    X11,
+    /// ask the terminal to set the clipboard, works over ssh
+    #[strum(serialize = "osc52")]
+    Osc52,
+}
+
+/// how to confirm running commands on production servers
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString)]
+pub enum ProdConfirmation {
+    #[strum(serialize = "none")]
+    None,
+    /// answer y to a prompt
+    #[strum(serialize = "confirm")]
+    Confirm,
+    /// type the name of the server
+    #[strum(serialize = "type_server_name")]
+    TypeServerName,
+}
+
+/// the columns of the list of actions
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString)]
+pub enum Column {
+    #[strum(serialize = "project")]
+    Project,
+    #[strum(serialize = "env")]
+    Env,
+    #[strum(serialize = "type")]
+    Type,
+    #[strum(serialize = "server")]
+    Server,
+    #[strum(serialize = "desc")]
+    Desc,
+    #[strum(serialize = "action")]
+    Action,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColumnWidth {
+    Fixed(usize),
+    /// share the terminal width left by the fixed columns
+    Auto,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width: ColumnWidth,
+}
+
+impl Column {
+    fn default_width(self) -> usize {
+        match self {
+            Column::Project => 7,
+            Column::Env => 3,
+            Column::Type => 4,
+            Column::Server => 30,
+            Column::Desc => 25,
+            Column::Action => 10,
+        }
+    }
+}
+
+/// the keys to sort the list of actions by, in order
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString)]
+pub enum SortKey {
+    /// most used actions first
+    #[strum(serialize = "history")]
+    History,
+    #[strum(serialize = "project")]
+    Project,
+    #[strum(serialize = "env")]
+    Env,
+    #[strum(serialize = "server")]
+    Server,
+    #[strum(serialize = "type")]
+    Type,
+    #[strum(serialize = "text")]
+    Text,
+}
+
+/// the ANSI SGR parameters to display each environment with,
+/// for instance 31;1 for bold red
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EnvColors {
+    pub dev: String,
+    pub stg: String,
+    pub uat: String,
+    pub prd: String,
+}
+
+impl Default for EnvColors {
+    fn default() -> Self {
+        EnvColors {
+            dev: "32;1".to_string(),
+            stg: "34;1".to_string(),
+            uat: "33;1".to_string(),
+            prd: "31;1".to_string(),
+        }
+    }
+}
+
+/// the user configuration for ppcli, in a ppcli.conf file
+/// containing key=value lines. Lines starting with # are comments.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CliConfig {
+    pub paste_method: PasteMethod,
+    pub clipboard_method: ClipboardMethod,
+    /// clear passwords from the clipboard after that many seconds. 0 to disable
+    pub clipboard_clear_secs: u64,
+    pub prod_confirmation: ProdConfirmation,
+    /// refuse to run command POIs and note snippets on production without --allow-prod
+    pub prod_commands_require_allow_prod: bool,
+    pub columns: Vec<ColumnSpec>,
+    pub sort: Vec<SortKey>,
+    /// a command printing the database password, when there is no keyring
+    pub password_command: Option<String>,
+    /// when the user types the database password, keep it in memory
+    /// for that many seconds. 0 to disable
+    pub password_cache_secs: u64,
+    /// rank first the actions run from the current folder
+    pub history_by_folder: bool,
+    /// remove the ssh keys handed to the shell after that many seconds
+    pub auth_key_lifetime_secs: u64,
+    /// false is the same as --no-color
+    pub color: bool,
+    pub env_colors: EnvColors,
+    /// when ppcli runs in one of these folders (or below), only
+    /// show the items of the matching project
+    pub folder_projects: Vec<(PathBuf, String)>,
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        CliConfig {
+            paste_method: PasteMethod::Auto,
+            clipboard_method: ClipboardMethod::Auto,
+            clipboard_clear_secs: 30,
+            prod_confirmation: ProdConfirmation::Confirm,
+            prod_commands_require_allow_prod: false,
+            columns: [
+                Column::Project,
+                Column::Env,
+                Column::Type,
+                Column::Server,
+                Column::Desc,
+                Column::Action,
+            ]
+            .iter()
+            .map(|&column| ColumnSpec {
+                column,
+                width: ColumnWidth::Fixed(column.default_width()),
+            })
+            .collect(),
+            sort: vec![
+                SortKey::History,
+                SortKey::Project,
+                SortKey::Server,
+                SortKey::Type,
+                SortKey::Text,
+            ],
+            password_command: None,
+            password_cache_secs: 0,
+            history_by_folder: false,
+            auth_key_lifetime_secs: 600,
+            color: true,
+            env_colors: EnvColors::default(),
+            folder_projects: vec![],
+        }
+    }
+}
+
+impl CliConfig {
+    /// the project for that folder, the most specific entry wins
+    pub fn folder_project(&self, folder: &Path) -> Option<&str> {
+        self.folder_projects
+            .iter()
+            .filter(|(f, _)| folder.starts_with(f))
+            .max_by_key(|(f, _)| f.components().count())
+            .map(|(_, p)| p.as_str())
+    }
+}
+
+fn cli_config_path() -> PathBuf {
+    let mut path = projectpadsql::config_path();
+    path.push("ppcli.conf");
+    path
+}
+
+pub fn read_cli_config() -> Result<CliConfig, Box<dyn std::error::Error>> {
+    let file_path = cli_config_path();
+    if !file_path.exists() {
+        return Ok(CliConfig::default());
+    }
+    let mut contents = String::new();
+    BufReader::new(File::open(file_path)?).read_to_string(&mut contents)?;
+    Ok(parse_cli_config(&contents)?)
+}
+
+fn parse_cli_config(contents: &str) -> Result<CliConfig, String> {
+    let mut config = CliConfig::default();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => return Err(format!("invalid line in ppcli.conf: {}", line)),
+        };
+        match key {
+            "paste_method" => {
+                config.paste_method = PasteMethod::from_str(value)
+                    .map_err(|_| format!("invalid paste_method in ppcli.conf: {}", value))?
+            }
+            "clipboard_method" => {
+                config.clipboard_method = ClipboardMethod::from_str(value)
+                    .map_err(|_| format!("invalid clipboard_method in ppcli.conf: {}", value))?
+            }
+            "clipboard_clear_secs" => {
+                config.clipboard_clear_secs = value
+                    .parse()
+                    .map_err(|_| format!("invalid clipboard_clear_secs in ppcli.conf: {}", value))?
+            }
+            "auth_key_lifetime_secs" => {
+                config.auth_key_lifetime_secs = value.parse().map_err(|_| {
+                    format!("invalid auth_key_lifetime_secs in ppcli.conf: {}", value)
+                })?
+            }
+            "prod_confirmation" => {
+                config.prod_confirmation = ProdConfirmation::from_str(value)
+                    .map_err(|_| format!("invalid prod_confirmation in ppcli.conf: {}", value))?
+            }
+            "prod_commands_require_allow_prod" => {
+                config.prod_commands_require_allow_prod = parse_config_bool(key, value)?
+            }
+            "columns" => config.columns = parse_columns(value)?,
+            "sort" => {
+                config.sort = parse_config_list(value)
+                    .map(|k| {
+                        SortKey::from_str(k)
+                            .map_err(|_| format!("invalid sort key in ppcli.conf: {}", k))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            "password_command" => config.password_command = Some(value.to_string()),
+            "password_cache_secs" => {
+                config.password_cache_secs = value
+                    .parse()
+                    .map_err(|_| format!("invalid password_cache_secs in ppcli.conf: {}", value))?
+            }
+            "history_by_folder" => config.history_by_folder = parse_config_bool(key, value)?,
+            "color" => config.color = parse_config_bool(key, value)?,
+            "color_dev" => config.env_colors.dev = parse_config_color(key, value)?,
+            "color_stg" => config.env_colors.stg = parse_config_color(key, value)?,
+            "color_uat" => config.env_colors.uat = parse_config_color(key, value)?,
+            "color_prd" => config.env_colors.prd = parse_config_color(key, value)?,
+            "folder_project" => config.folder_projects.push(parse_folder_project(value)?),
+            _ => return Err(format!("unknown key in ppcli.conf: {}", key)),
+        }
+    }
+    Ok(config)
+}
+
+fn parse_config_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// columns=project:12,env,server:auto,desc:auto,action
+fn parse_columns(value: &str) -> Result<Vec<ColumnSpec>, String> {
+    parse_config_list(value)
+        .map(|col| {
+            let (name, width) = match col.split_once(':') {
+                Some((n, w)) => (n.trim(), Some(w.trim())),
+                None => (col, None),
+            };
+            let column = Column::from_str(name)
+                .map_err(|_| format!("invalid column in ppcli.conf: {}", name))?;
+            let width = match width {
+                None => ColumnWidth::Fixed(column.default_width()),
+                Some("auto") => ColumnWidth::Auto,
+                Some(w) => ColumnWidth::Fixed(
+                    w.parse()
+                        .map_err(|_| format!("invalid column width in ppcli.conf: {}", w))?,
+                ),
+            };
+            Ok(ColumnSpec { column, width })
+        })
+        .collect()
+}
+
+/// folder_project=~/src/clientx:ClientX
+fn parse_folder_project(value: &str) -> Result<(PathBuf, String), String> {
+    match value.split_once(':') {
+        Some((folder, project)) if !folder.trim().is_empty() && !project.trim().is_empty() => Ok((
+            placeholders::expand_tilde(folder.trim()),
+            project.trim().to_string(),
+        )),
+        _ => Err(format!(
+            "invalid folder_project in ppcli.conf, expected folder:project: {}",
+            value
+        )),
+    }
+}
+
+fn parse_config_color(key: &str, value: &str) -> Result<String, String> {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit() || c == ';') {
+        Ok(value.to_string())
+    } else {
+        Err(format!("invalid {} in ppcli.conf: {}", key, value))
+    }
+}
+
+fn parse_config_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" => Ok(true),
+        "false" | "no" => Ok(false),
+        _ => Err(format!("invalid {} in ppcli.conf: {}", key, value)),
+    }
+}
 
 fn upgrade_check_time_path() -> PathBuf {
     let mut path = projectpadsql::config_path();
@@ -66,7 +418,7 @@ fn actions_file_path() -> PathBuf {
     path
 }
 
-pub fn read_action_history() -> Result<Vec<ExecutedAction>, std::io::Error> {
+pub fn read_action_history() -> Result<Vec<ActionHistoryEntry>, std::io::Error> {
     let actions_file = File::open(actions_file_path())?;
     BufReader::new(actions_file)
         .lines()
@@ -79,52 +431,120 @@ pub fn read_string_history() -> Result<Vec<String>, std::io::Error> {
     BufReader::new(hist_file).lines().collect()
 }
 
-fn parse_action_history_line(line: &str) -> Result<ExecutedAction, std::io::Error> {
+fn history_parse_error(line: &str) -> std::io::Error {
+    std::io::Error::other(format!("couldn't parse {}", line))
+}
+
+/// the history lines are:
+/// item type;item id;action;timestamp;folder[;placeholder=value]*
+/// (timestamp and folder may be empty)
+/// Older versions of ppcli only wrote: item type;item id;action
+fn parse_action_history_line(line: &str) -> Result<ActionHistoryEntry, std::io::Error> {
     let elts: Vec<_> = line.split(';').collect();
-    match (
-        elts.len(),
-        elts.get(0),
+    let item = match (
+        elts.first(),
         elts.get(1).and_then(|i| i.parse::<i32>().ok()),
-        elts.get(2).and_then(|a| ActionType::from_str(a).ok()),
     ) {
-        (3, Some(&"S"), Some(id), Some(action_desc)) => {
-            Ok(ExecutedAction::new(LinkedItemId::Server(id), action_desc))
-        }
-        (3, Some(&"P"), Some(id), Some(action_desc)) => Ok(ExecutedAction::new(
-            LinkedItemId::ProjectPoi(id),
-            action_desc,
-        )),
-        (3, Some(&"SP"), Some(id), Some(action_desc)) => Ok(ExecutedAction::new(
-            LinkedItemId::ServerPoi(id),
-            action_desc,
-        )),
-        _ => Err(std::io::Error::new(
-            ErrorKind::Other,
-            format!("couldn't parse {}", line),
-        )),
+        (Some(&"S"), Some(id)) => LinkedItemId::Server(id),
+        (Some(&"P"), Some(id)) => LinkedItemId::ProjectPoi(id),
+        (Some(&"SP"), Some(id)) => LinkedItemId::ServerPoi(id),
+        (Some(&"SD"), Some(id)) => LinkedItemId::ServerDatabase(id),
+        (Some(&"SW"), Some(id)) => LinkedItemId::ServerWebsite(id),
+        (Some(&"SU"), Some(id)) => LinkedItemId::ServerExtraUserAccount(id),
+        (Some(&"PN"), Some(id)) => LinkedItemId::ProjectNote(id),
+        (Some(&"SN"), Some(id)) => LinkedItemId::ServerNote(id),
+        _ => return Err(history_parse_error(line)),
+    };
+    let action_desc = elts
+        .get(2)
+        .and_then(|a| ActionType::from_str(a).ok())
+        .ok_or_else(|| history_parse_error(line))?;
+    if elts.len() == 3 {
+        return Ok(ActionHistoryEntry {
+            action: ExecutedAction::new(item, action_desc),
+            placeholder_values: vec![],
+            timestamp: None,
+            folder: None,
+        });
     }
+    // the timestamp is empty for entries migrated from the old format
+    let timestamp = match elts[3] {
+        "" => None,
+        t => Some(t.parse::<u64>().map_err(|_| history_parse_error(line))?),
+    };
+    let folder = elts
+        .get(4)
+        .filter(|f| !f.is_empty())
+        .map(|f| PathBuf::from(unescape_history_field(f)));
+    let placeholder_values = elts[5.min(elts.len())..]
+        .iter()
+        .map(|v| {
+            v.split_once('=')
+                .map(|(p, v)| (unescape_history_field(p), unescape_history_field(v)))
+                .ok_or_else(|| history_parse_error(line))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(ActionHistoryEntry {
+        action: ExecutedAction::new(item, action_desc),
+        placeholder_values,
+        timestamp,
+        folder,
+    })
 }
 
-fn serialize_action_history_line(action: &ExecutedAction) -> String {
-    match action {
-        ExecutedAction {
-            item: LinkedItemId::Server(id),
-            action_desc,
-        } => format!("S;{};{}", id, action_desc),
-        ExecutedAction {
-            item: LinkedItemId::ProjectPoi(id),
-            action_desc,
-        } => format!("P;{};{}", id, action_desc),
-        ExecutedAction {
-            item: LinkedItemId::ServerPoi(id),
-            action_desc,
-        } => format!("SP;{};{}", id, action_desc),
+fn serialize_action_history_line(entry: &ActionHistoryEntry) -> String {
+    let (item_type, id) = match entry.action.item {
+        LinkedItemId::Server(id) => ("S", id),
+        LinkedItemId::ProjectPoi(id) => ("P", id),
+        LinkedItemId::ServerPoi(id) => ("SP", id),
+        LinkedItemId::ServerDatabase(id) => ("SD", id),
+        LinkedItemId::ServerWebsite(id) => ("SW", id),
+        LinkedItemId::ServerExtraUserAccount(id) => ("SU", id),
+        LinkedItemId::ProjectNote(id) => ("PN", id),
+        LinkedItemId::ServerNote(id) => ("SN", id),
+    };
+    let mut line = format!(
+        "{};{};{};{};{}",
+        item_type,
+        id,
+        entry.action.action_desc,
+        entry.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+        entry
+            .folder
+            .as_ref()
+            .map(|f| escape_history_field(&f.to_string_lossy()))
+            .unwrap_or_default()
+    );
+    for (placeholder, value) in &entry.placeholder_values {
+        line.push(';');
+        line.push_str(&escape_history_field(placeholder));
+        line.push('=');
+        line.push_str(&escape_history_field(value));
     }
+    line
+}
+
+// the placeholder values are typed by the user and can contain
+// our separators
+fn escape_history_field(field: &str) -> String {
+    field
+        .replace('%', "%25")
+        .replace(';', "%3B")
+        .replace('=', "%3D")
+        .replace('\n', "%0A")
+}
+
+fn unescape_history_field(field: &str) -> String {
+    field
+        .replace("%0A", "\n")
+        .replace("%3D", "=")
+        .replace("%3B", ";")
+        .replace("%25", "%")
 }
 
 pub fn write_actions_history(
-    orig_actions: &[ExecutedAction],
-    latest: ExecutedAction,
+    orig_actions: &[ActionHistoryEntry],
+    latest: ActionHistoryEntry,
     limit: usize,
 ) -> Result<(), std::io::Error> {
     write_history(
@@ -171,3 +591,115 @@ fn write_history(
     file.write_all(history.join("\n").as_bytes())?;
     Ok(())
 }
+
+#[test]
+fn parse_cli_config_paste_method() {
+    assert_eq!(
+        Ok(CliConfig {
+            paste_method: PasteMethod::Tmux,
+            ..CliConfig::default()
+        }),
+        parse_cli_config("# my config\n\npaste_method = tmux\n")
+    );
+    assert!(parse_cli_config("paste_method=xdotool").is_err());
+    assert!(parse_cli_config("other=1").is_err());
+}
+
+#[test]
+fn parse_cli_config_prod_guard() {
+    let config = parse_cli_config(
+        "prod_confirmation=type_server_name\nprod_commands_require_allow_prod=yes",
+    )
+    .unwrap();
+    assert_eq!(ProdConfirmation::TypeServerName, config.prod_confirmation);
+    assert!(config.prod_commands_require_allow_prod);
+    assert!(parse_cli_config("prod_commands_require_allow_prod=maybe").is_err());
+}
+
+#[test]
+fn parse_cli_config_display() {
+    let config = parse_cli_config(
+        "columns=project:12, env, desc:auto,action\nsort=project,history\ncolor_prd=35;4",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            ColumnSpec {
+                column: Column::Project,
+                width: ColumnWidth::Fixed(12)
+            },
+            ColumnSpec {
+                column: Column::Env,
+                width: ColumnWidth::Fixed(3)
+            },
+            ColumnSpec {
+                column: Column::Desc,
+                width: ColumnWidth::Auto
+            },
+            ColumnSpec {
+                column: Column::Action,
+                width: ColumnWidth::Fixed(10)
+            },
+        ],
+        config.columns
+    );
+    assert_eq!(vec![SortKey::Project, SortKey::History], config.sort);
+    assert_eq!("35;4", config.env_colors.prd);
+    assert!(parse_cli_config("columns=project,ip").is_err());
+    assert!(parse_cli_config("color_dev=red").is_err());
+}
+
+#[test]
+fn parse_cli_config_password() {
+    let config =
+        parse_cli_config("password_command=pass show projectpad\npassword_cache_secs=600").unwrap();
+    assert_eq!(
+        Some("pass show projectpad".to_string()),
+        config.password_command
+    );
+    assert_eq!(600, config.password_cache_secs);
+    assert!(parse_cli_config("password_cache_secs=-1").is_err());
+}
+
+#[test]
+fn parse_cli_config_folder_projects() {
+    let config =
+        parse_cli_config("folder_project=/src/client:Client\nfolder_project=/src/client/web:Web")
+            .unwrap();
+    assert_eq!(
+        Some("Web"),
+        config.folder_project(Path::new("/src/client/web/assets"))
+    );
+    assert_eq!(
+        Some("Client"),
+        config.folder_project(Path::new("/src/client"))
+    );
+    assert_eq!(None, config.folder_project(Path::new("/src/client2")));
+    assert!(parse_cli_config("folder_project=/src/client").is_err());
+}
+
+#[test]
+fn action_history_line_roundtrip() {
+    let entry = ActionHistoryEntry {
+        action: ExecutedAction::new(LinkedItemId::ServerPoi(12), ActionType::RunCmd),
+        placeholder_values: vec![
+            ("ask:ticket id".to_string(), "T-1;a=b%".to_string()),
+            ("date".to_string(), "2021-03-25".to_string()),
+        ],
+        timestamp: Some(1616630400),
+        folder: Some(PathBuf::from("/home/me/a;b")),
+    };
+    let line = serialize_action_history_line(&entry);
+    assert_eq!(
+        "SP;12;run cmd;1616630400;/home/me/a%3Bb;ask:ticket id=T-1%3Ba%3Db%25;date=2021-03-25",
+        line
+    );
+    assert_eq!(entry, parse_action_history_line(&line).unwrap());
+    // the format of older versions of ppcli
+    let legacy = parse_action_history_line("S;3;ssh shell").unwrap();
+    assert_eq!(
+        ExecutedAction::new(LinkedItemId::Server(3), ActionType::SshShell),
+        legacy.action
+    );
+    assert_eq!(None, legacy.timestamp);
+}
@@ -1,9 +1,13 @@
 use super::actions;
+use super::authkeys;
+use crate::config::{CliConfig, Column, ColumnSpec, ColumnWidth, EnvColors, SortKey};
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 use projectpadsql::models::*;
 use skim::prelude::*;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
 use std::string::ToString;
 use strum_macros::{Display, EnumString};
@@ -14,14 +18,72 @@ pub enum ItemType {
     // type for sorting of the display
     ServerItemType(ServerType),
     InterestItemType(InterestType),
+    Database,
+    Website,
+    /// a project or server note, to view in full
+    Note,
+    /// a code block of a note
+    Snippet,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Debug is implemented by hand, not to print the password
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ServerInfo {
+    // the order of the fields is the sort order
     pub server_desc: String,
     pub server_username: String,
     pub server_ip: String,
+    pub server_id: i32,
     pub server_access_type: ServerAccessType,
+    /// the chain of jump hosts to go through to reach the server
+    pub ssh_jump_hosts: Vec<JumpHost>,
+    pub ssh_auth_key: Option<SshAuthKey>,
+    /// the group of the server in the project, if any
+    pub server_group: Option<String>,
+    /// the password of server_username. Only loaded for servers
+    /// and extra user accounts, empty otherwise
+    pub server_password: String,
+}
+
+impl fmt::Debug for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerInfo")
+            .field("server_desc", &self.server_desc)
+            .field("server_username", &self.server_username)
+            .field("server_ip", &self.server_ip)
+            .field("server_id", &self.server_id)
+            .field("server_access_type", &self.server_access_type)
+            .field("ssh_jump_hosts", &self.ssh_jump_hosts)
+            .field("ssh_auth_key", &self.ssh_auth_key)
+            .field("server_group", &self.server_group)
+            .field("server_password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// a server to go through to reach another server
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JumpHost {
+    /// in `[user@]host[:port]` format, as expected by ssh's -J
+    pub spec: String,
+    pub ssh_auth_key: Option<SshAuthKey>,
+}
+
+/// Debug is implemented by hand, not to print the key
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SshAuthKey {
+    /// where the key gets written before we run ssh
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+}
+
+impl fmt::Debug for SshAuthKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SshAuthKey")
+            .field("path", &self.path)
+            .field("contents", &"<redacted>")
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -29,11 +91,54 @@ pub struct PoiInfo {
     pub path: PathBuf,
 }
 
+/// Debug is implemented by hand, not to print the password
+#[derive(Clone)]
+pub struct DbInfo {
+    pub db_name: String,
+    pub db_text: String,
+    pub db_username: String,
+    pub db_password: String,
+}
+
+impl fmt::Debug for DbInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbInfo")
+            .field("db_name", &self.db_name)
+            .field("db_text", &self.db_text)
+            .field("db_username", &self.db_username)
+            .field("db_password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// the URL of the website is the item text. Debug is implemented
+/// by hand, not to print the password
+#[derive(Clone)]
+pub struct WebsiteInfo {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for WebsiteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebsiteInfo")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
 #[derive(PartialEq, Eq, Copy, Clone, PartialOrd, Ord, Hash, Debug)]
 pub enum LinkedItemId {
     Server(i32),
     ServerPoi(i32),
     ProjectPoi(i32),
+    ServerDatabase(i32),
+    ServerWebsite(i32),
+    ServerExtraUserAccount(i32),
+    /// the note and all its snippets share the id of the note
+    ProjectNote(i32),
+    ServerNote(i32),
 }
 
 #[derive(Debug, Clone)]
@@ -46,13 +151,138 @@ pub struct ItemOfInterest {
     pub item_text: String,
     pub server_info: Option<ServerInfo>,
     pub poi_info: Option<PoiInfo>,
+    pub db_info: Option<DbInfo>,
+    pub website_info: Option<WebsiteInfo>,
     pub run_on: Option<RunOn>,
 }
 
-fn filter_servers(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
-    use projectpadsql::schema::project::dsl as prj;
+/// restricts the items loaded from the database
+#[derive(Default, Clone, Debug)]
+pub struct ItemsFilter {
+    /// the project name, matched with the sqlite like, which ignores the case
+    pub project: Option<String>,
+    pub env: Option<EnvironmentType>,
+    /// the type as displayed in the list: LOG, CFG, SRA...
+    pub item_type: Option<String>,
+}
+
+impl ItemsFilter {
+    fn matches_type(&self, item_type: &ItemType) -> bool {
+        self.item_type
+            .as_ref()
+            .map(|t| t.eq_ignore_ascii_case(render_type(item_type)))
+            .unwrap_or(true)
+    }
+}
+
+struct JumpHostInfo {
+    username: String,
+    ip: String,
+    access_type: ServerAccessType,
+    ssh_tunnel_through_server_id: Option<i32>,
+    auth_key: Option<Vec<u8>>,
+}
+
+fn load_jump_hosts_info(db_conn: &mut SqliteConnection) -> HashMap<i32, JumpHostInfo> {
     use projectpadsql::schema::server::dsl as srv;
     srv::server
+        .select((
+            srv::id,
+            srv::username,
+            srv::ip,
+            srv::access_type,
+            srv::ssh_tunnel_through_server_id,
+            srv::auth_key,
+        ))
+        .load::<(
+            i32,
+            String,
+            String,
+            ServerAccessType,
+            Option<i32>,
+            Option<Vec<u8>>,
+        )>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(
+            |(id, username, ip, access_type, ssh_tunnel_through_server_id, auth_key)| {
+                (
+                    id,
+                    JumpHostInfo {
+                        username,
+                        ip,
+                        access_type,
+                        ssh_tunnel_through_server_id,
+                        auth_key,
+                    },
+                )
+            },
+        )
+        .collect()
+}
+
+/// resolve the chain of servers to go through to reach a server accessed
+/// through a SSH tunnel. The first element is the first server to connect to.
+/// We don't need the ssh_tunnel_port: with ProxyJump, ssh takes care of
+/// the forwarding without any local port.
+fn resolve_ssh_jump_hosts(
+    jump_hosts_info: &HashMap<i32, JumpHostInfo>,
+    access_type: ServerAccessType,
+    ssh_tunnel_through_server_id: Option<i32>,
+) -> Vec<JumpHost> {
+    let mut result = vec![];
+    if access_type != ServerAccessType::SrvAccessSshTunnel {
+        return result;
+    }
+    let mut visited = vec![];
+    let mut cur_id = ssh_tunnel_through_server_id;
+    while let Some(id) = cur_id {
+        if visited.contains(&id) {
+            // a loop in the tunnel configuration, give up on it
+            break;
+        }
+        visited.push(id);
+        match jump_hosts_info.get(&id) {
+            Some(info) => {
+                result.push(JumpHost {
+                    spec: jump_host_spec(&info.username, &info.ip),
+                    ssh_auth_key: server_auth_key(id, info.auth_key.clone()),
+                });
+                // the jump host may itself be reachable only through a tunnel
+                cur_id = Some(info)
+                    .filter(|i| i.access_type == ServerAccessType::SrvAccessSshTunnel)
+                    .and_then(|i| i.ssh_tunnel_through_server_id);
+            }
+            None => break,
+        }
+    }
+    result.reverse();
+    result
+}
+
+fn server_auth_key(server_id: i32, auth_key: Option<Vec<u8>>) -> Option<SshAuthKey> {
+    auth_key.map(|contents| SshAuthKey {
+        path: authkeys::auth_key_path("server", server_id),
+        contents,
+    })
+}
+
+fn jump_host_spec(username: &str, ip: &str) -> String {
+    let user_param = if username.is_empty() {
+        "".to_string()
+    } else {
+        format!("{}@", username)
+    };
+    // the server IP can include a port (host:port), which is also the
+    // syntax that -J expects
+    format!("{}{}", user_param, ip.trim_end_matches(":22"))
+}
+
+fn filter_servers(db_conn: &mut SqliteConnection, filter: &ItemsFilter) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    let jump_hosts_info = load_jump_hosts_info(db_conn);
+    let mut query = srv::server
         .inner_join(prj::project)
         .select((
             srv::id,
@@ -63,12 +293,20 @@ fn filter_servers(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
             srv::server_type,
             srv::username,
             srv::access_type,
+            srv::ssh_tunnel_through_server_id,
+            srv::auth_key,
+            srv::group_name,
+            srv::password,
         ))
-        .filter(srv::access_type.ne_all(vec![
-            ServerAccessType::SrvAccessRdp,
-            ServerAccessType::SrvAccessWww,
-        ]))
-        .load::<(_, _, String, _, String, _, _, _)>(db_conn)
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    if let Some(env) = filter.env {
+        query = query.filter(srv::environment.eq(env));
+    }
+    query
+        .load::<(_, _, String, _, String, _, _, _, _, _, _, _)>(db_conn)
         .unwrap()
         .into_iter()
         .map(
@@ -81,6 +319,10 @@ fn filter_servers(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
                 server_type,
                 server_username,
                 server_access_type,
+                ssh_tunnel_through_server_id,
+                auth_key,
+                server_group,
+                server_password,
             )| {
                 ItemOfInterest {
                     linked_item: LinkedItemId::Server(id),
@@ -90,23 +332,42 @@ fn filter_servers(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
                     poi_desc: Some(server_desc.clone()),
                     item_text: server_ip.clone(),
                     server_info: Some(ServerInfo {
+                        server_id: id,
                         server_desc,
                         server_username,
                         server_ip,
                         server_access_type,
+                        ssh_jump_hosts: resolve_ssh_jump_hosts(
+                            &jump_hosts_info,
+                            server_access_type,
+                            ssh_tunnel_through_server_id,
+                        ),
+                        ssh_auth_key: server_auth_key(id, auth_key),
+                        server_group,
+                        server_password,
                     }),
                     poi_info: None,
+                    db_info: None,
+                    website_info: None,
                     run_on: None,
                 }
             },
         )
+        .filter(|i| filter.matches_type(&i.item_type))
         .collect()
 }
 
-fn filter_project_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
+fn filter_project_pois(
+    db_conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
     use projectpadsql::schema::project::dsl as prj;
     use projectpadsql::schema::project_point_of_interest::dsl as prj_poi;
-    prj_poi::project_point_of_interest
+    if filter.env.is_some() {
+        // project items don't belong to an environment
+        return vec![];
+    }
+    let mut query = prj_poi::project_point_of_interest
         .inner_join(prj::project)
         .select((
             prj_poi::id,
@@ -116,6 +377,11 @@ fn filter_project_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
             prj_poi::interest_type,
             prj_poi::path,
         ))
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    query
         .load::<(_, _, _, _, _, String)>(db_conn)
         .unwrap()
         .into_iter()
@@ -132,18 +398,22 @@ fn filter_project_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
                     poi_info: Some(PoiInfo {
                         path: prj_path.into(),
                     }),
+                    db_info: None,
+                    website_info: None,
                     run_on: None,
                 }
             },
         )
+        .filter(|i| filter.matches_type(&i.item_type))
         .collect()
 }
 
-fn filter_server_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
+fn filter_server_pois(db_conn: &mut SqliteConnection, filter: &ItemsFilter) -> Vec<ItemOfInterest> {
     use projectpadsql::schema::project::dsl as prj;
     use projectpadsql::schema::server::dsl as srv;
     use projectpadsql::schema::server_point_of_interest::dsl as srv_poi;
-    srv_poi::server_point_of_interest
+    let jump_hosts_info = load_jump_hosts_info(db_conn);
+    let mut query = srv_poi::server_point_of_interest
         .inner_join(srv::server.inner_join(prj::project))
         .select((
             srv_poi::id,
@@ -158,12 +428,24 @@ fn filter_server_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
             srv::access_type,
             srv::ip,
             srv_poi::run_on,
+            srv::ssh_tunnel_through_server_id,
+            srv::id,
+            srv::auth_key,
+            srv::group_name,
         ))
         .filter(srv::access_type.ne_all(vec![
             ServerAccessType::SrvAccessRdp,
             ServerAccessType::SrvAccessWww,
         ]))
-        .load::<(_, _, _, _, _, _, _, _, String, _, _, _)>(db_conn)
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    if let Some(env) = filter.env {
+        query = query.filter(srv::environment.eq(env));
+    }
+    query
+        .load::<(_, _, _, _, _, _, _, _, String, _, _, _, _, _, _, _)>(db_conn)
         .unwrap()
         .into_iter()
         .map(
@@ -180,6 +462,10 @@ fn filter_server_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
                 server_access_type,
                 server_ip,
                 run_on_val,
+                ssh_tunnel_through_server_id,
+                server_id,
+                auth_key,
+                server_group,
             )| {
                 ItemOfInterest {
                     linked_item: LinkedItemId::ServerPoi(id),
@@ -189,21 +475,569 @@ fn filter_server_pois(db_conn: &mut SqliteConnection) -> Vec<ItemOfInterest> {
                     poi_desc: Some(server_poi_desc),
                     item_text,
                     server_info: Some(ServerInfo {
+                        server_id,
                         server_desc,
                         server_username,
                         server_ip,
                         server_access_type,
+                        ssh_jump_hosts: resolve_ssh_jump_hosts(
+                            &jump_hosts_info,
+                            server_access_type,
+                            ssh_tunnel_through_server_id,
+                        ),
+                        ssh_auth_key: server_auth_key(server_id, auth_key),
+                        server_group,
+                        server_password: "".to_string(),
                     }),
                     poi_info: Some(PoiInfo {
                         path: srv_poi_path.into(),
                     }),
+                    db_info: None,
+                    website_info: None,
                     run_on: Some(run_on_val),
                 }
             },
         )
+        .filter(|i| filter.matches_type(&i.item_type))
+        .collect()
+}
+
+fn filter_server_databases(
+    db_conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_database::dsl as db;
+    let jump_hosts_info = load_jump_hosts_info(db_conn);
+    let mut query = db::server_database
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            (
+                db::id,
+                db::desc,
+                db::name,
+                db::text,
+                db::username,
+                db::password,
+            ),
+            prj::name,
+            srv::desc,
+            srv::environment,
+            srv::username,
+            srv::access_type,
+            srv::ip,
+            srv::ssh_tunnel_through_server_id,
+            (srv::id, srv::auth_key, srv::group_name),
+        ))
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    if let Some(env) = filter.env {
+        query = query.filter(srv::environment.eq(env));
+    }
+    query
+        .load::<(
+            (i32, String, String, String, String, String),
+            _,
+            _,
+            _,
+            _,
+            _,
+            String,
+            _,
+            _,
+        )>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(
+            |(
+                (id, db_desc, db_name, db_text, db_username, db_password),
+                project_name,
+                server_desc,
+                srv_env,
+                server_username,
+                server_access_type,
+                server_ip,
+                ssh_tunnel_through_server_id,
+                (server_id, auth_key, server_group),
+            )| {
+                ItemOfInterest {
+                    linked_item: LinkedItemId::ServerDatabase(id),
+                    project_name,
+                    env: Some(srv_env),
+                    item_type: ItemType::Database,
+                    poi_desc: Some(db_desc),
+                    item_text: db_name.clone(),
+                    server_info: Some(ServerInfo {
+                        server_id,
+                        server_desc,
+                        server_username,
+                        server_ip,
+                        server_access_type,
+                        ssh_jump_hosts: resolve_ssh_jump_hosts(
+                            &jump_hosts_info,
+                            server_access_type,
+                            ssh_tunnel_through_server_id,
+                        ),
+                        ssh_auth_key: server_auth_key(server_id, auth_key),
+                        server_group,
+                        server_password: "".to_string(),
+                    }),
+                    poi_info: None,
+                    db_info: Some(DbInfo {
+                        db_name,
+                        db_text,
+                        db_username,
+                        db_password,
+                    }),
+                    website_info: None,
+                    run_on: None,
+                }
+            },
+        )
+        .filter(|i| filter.matches_type(&i.item_type))
+        .collect()
+}
+
+fn filter_server_websites(
+    db_conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_website::dsl as www;
+    let mut query = www::server_website
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            www::id,
+            prj::name,
+            srv::desc,
+            www::desc,
+            srv::environment,
+            www::url,
+            www::username,
+            www::password,
+            srv::id,
+            srv::group_name,
+        ))
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    if let Some(env) = filter.env {
+        query = query.filter(srv::environment.eq(env));
+    }
+    query
+        .load::<(_, _, String, _, _, String, _, _, _, _)>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(
+            |(
+                id,
+                project_name,
+                server_desc,
+                www_desc,
+                srv_env,
+                url,
+                username,
+                password,
+                server_id,
+                server_group,
+            )| {
+                ItemOfInterest {
+                    linked_item: LinkedItemId::ServerWebsite(id),
+                    project_name,
+                    env: Some(srv_env),
+                    item_type: ItemType::Website,
+                    poi_desc: Some(www_desc),
+                    item_text: url,
+                    // we don't connect to the server for websites, we only
+                    // need the server for display
+                    server_info: Some(ServerInfo {
+                        server_id,
+                        server_desc,
+                        server_username: "".to_string(),
+                        server_ip: "".to_string(),
+                        server_access_type: ServerAccessType::SrvAccessWww,
+                        ssh_jump_hosts: vec![],
+                        ssh_auth_key: None,
+                        server_group,
+                        server_password: "".to_string(),
+                    }),
+                    poi_info: None,
+                    db_info: None,
+                    website_info: Some(WebsiteInfo { username, password }),
+                    run_on: None,
+                }
+            },
+        )
+        .filter(|i| filter.matches_type(&i.item_type))
+        .collect()
+}
+
+fn filter_server_extra_users(
+    db_conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_extra_user_account::dsl as usr;
+    let jump_hosts_info = load_jump_hosts_info(db_conn);
+    let mut query = usr::server_extra_user_account
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            (
+                usr::id,
+                usr::username,
+                usr::desc,
+                usr::auth_key,
+                usr::password,
+            ),
+            prj::name,
+            srv::desc,
+            srv::environment,
+            srv::server_type,
+            srv::access_type,
+            srv::ip,
+            srv::ssh_tunnel_through_server_id,
+            (srv::id, srv::group_name),
+        ))
+        .filter(srv::access_type.eq_any(vec![
+            ServerAccessType::SrvAccessSsh,
+            ServerAccessType::SrvAccessSshTunnel,
+        ]))
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    if let Some(env) = filter.env {
+        query = query.filter(srv::environment.eq(env));
+    }
+    query
+        .load::<(
+            (i32, String, String, Option<Vec<u8>>, String),
+            _,
+            _,
+            _,
+            _,
+            _,
+            String,
+            _,
+            _,
+        )>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(
+            |(
+                (id, username, user_desc, auth_key, password),
+                project_name,
+                server_desc,
+                srv_env,
+                server_type,
+                server_access_type,
+                server_ip,
+                ssh_tunnel_through_server_id,
+                (server_id, server_group),
+            )| {
+                ItemOfInterest {
+                    linked_item: LinkedItemId::ServerExtraUserAccount(id),
+                    project_name,
+                    env: Some(srv_env),
+                    item_type: ItemType::ServerItemType(server_type),
+                    poi_desc: Some(if user_desc.is_empty() {
+                        username.clone()
+                    } else {
+                        format!("{} ({})", username, user_desc)
+                    }),
+                    item_text: server_ip.clone(),
+                    server_info: Some(ServerInfo {
+                        server_id,
+                        server_desc,
+                        // connect as the extra user instead of the main user
+                        server_username: username,
+                        server_ip,
+                        server_access_type,
+                        ssh_jump_hosts: resolve_ssh_jump_hosts(
+                            &jump_hosts_info,
+                            server_access_type,
+                            ssh_tunnel_through_server_id,
+                        ),
+                        ssh_auth_key: auth_key.map(|contents| SshAuthKey {
+                            path: authkeys::auth_key_path("extra-user", id),
+                            contents,
+                        }),
+                        server_group,
+                        server_password: password,
+                    }),
+                    poi_info: None,
+                    db_info: None,
+                    website_info: None,
+                    run_on: None,
+                }
+            },
+        )
+        .filter(|i| filter.matches_type(&i.item_type))
+        .collect()
+}
+
+/// a note gives an item to view the whole note, and an item per
+/// code block of the note
+fn note_items(note: ItemOfInterest, title: &str, contents: &str) -> Vec<ItemOfInterest> {
+    let snippets: Vec<_> = projectpadsql::notes::note_code_blocks(contents)
+        .into_iter()
+        .map(|snippet| ItemOfInterest {
+            item_type: ItemType::Snippet,
+            // several snippets of the note must be told apart in the list
+            poi_desc: Some(format!(
+                "{}: {}",
+                title,
+                snippet.lines().next().unwrap_or("")
+            )),
+            item_text: snippet,
+            ..note.clone()
+        })
+        .collect();
+    let mut items = vec![note];
+    items.extend(snippets);
+    items
+}
+
+fn filter_project_notes(
+    db_conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::project_note::dsl as prj_note;
+    let mut query = prj_note::project_note
+        .inner_join(prj::project)
+        .select((
+            prj_note::id,
+            prj::name,
+            prj_note::title,
+            prj_note::contents,
+            (
+                prj_note::has_dev,
+                prj_note::has_stage,
+                prj_note::has_uat,
+                prj_note::has_prod,
+            ),
+        ))
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    query = match filter.env {
+        Some(EnvironmentType::EnvDevelopment) => query.filter(prj_note::has_dev.eq(true)),
+        Some(EnvironmentType::EnvStage) => query.filter(prj_note::has_stage.eq(true)),
+        Some(EnvironmentType::EnvUat) => query.filter(prj_note::has_uat.eq(true)),
+        Some(EnvironmentType::EnvProd) => query.filter(prj_note::has_prod.eq(true)),
+        None => query,
+    };
+    query
+        .load::<(i32, String, String, String, (bool, bool, bool, bool))>(db_conn)
+        .unwrap()
+        .into_iter()
+        .flat_map(
+            |(id, project_name, title, contents, (has_dev, has_stage, has_uat, has_prod))| {
+                let envs: Vec<_> = [
+                    (has_dev, EnvironmentType::EnvDevelopment),
+                    (has_stage, EnvironmentType::EnvStage),
+                    (has_uat, EnvironmentType::EnvUat),
+                    (has_prod, EnvironmentType::EnvProd),
+                ]
+                .iter()
+                .filter(|(has_env, _)| *has_env)
+                .map(|(_, env)| *env)
+                .collect();
+                // the list has room for a single environment
+                let env = match envs[..] {
+                    [env] => Some(env),
+                    _ => filter.env,
+                };
+                let note = ItemOfInterest {
+                    linked_item: LinkedItemId::ProjectNote(id),
+                    project_name,
+                    env,
+                    item_type: ItemType::Note,
+                    poi_desc: Some(title.clone()),
+                    item_text: projectpadsql::notes::note_markdown_to_quick_preview(&contents)
+                        .trim()
+                        .to_string(),
+                    server_info: None,
+                    poi_info: None,
+                    db_info: None,
+                    website_info: None,
+                    run_on: None,
+                };
+                note_items(note, &title, &contents)
+            },
+        )
+        .filter(|i| filter.matches_type(&i.item_type))
+        .collect()
+}
+
+fn filter_server_notes(
+    db_conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_note::dsl as srv_note;
+    let jump_hosts_info = load_jump_hosts_info(db_conn);
+    let mut query = srv_note::server_note
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            srv_note::id,
+            prj::name,
+            srv_note::title,
+            srv_note::contents,
+            srv::environment,
+            (
+                srv::id,
+                srv::desc,
+                srv::username,
+                srv::ip,
+                srv::access_type,
+                srv::ssh_tunnel_through_server_id,
+                srv::auth_key,
+                srv::group_name,
+            ),
+        ))
+        .into_boxed();
+    if let Some(project) = &filter.project {
+        query = query.filter(prj::name.like(project.as_str()));
+    }
+    if let Some(env) = filter.env {
+        query = query.filter(srv::environment.eq(env));
+    }
+    query
+        .load::<(i32, String, String, String, _, (i32, _, _, _, _, _, _, _))>(db_conn)
+        .unwrap()
+        .into_iter()
+        .flat_map(
+            |(
+                id,
+                project_name,
+                title,
+                contents,
+                srv_env,
+                (
+                    server_id,
+                    server_desc,
+                    server_username,
+                    server_ip,
+                    server_access_type,
+                    ssh_tunnel_through_server_id,
+                    auth_key,
+                    server_group,
+                ),
+            )| {
+                let note = ItemOfInterest {
+                    linked_item: LinkedItemId::ServerNote(id),
+                    project_name,
+                    env: Some(srv_env),
+                    item_type: ItemType::Note,
+                    poi_desc: Some(title.clone()),
+                    item_text: projectpadsql::notes::note_markdown_to_quick_preview(&contents)
+                        .trim()
+                        .to_string(),
+                    server_info: Some(ServerInfo {
+                        server_id,
+                        server_desc,
+                        server_username,
+                        server_ip,
+                        server_access_type,
+                        ssh_jump_hosts: resolve_ssh_jump_hosts(
+                            &jump_hosts_info,
+                            server_access_type,
+                            ssh_tunnel_through_server_id,
+                        ),
+                        ssh_auth_key: server_auth_key(server_id, auth_key),
+                        server_group,
+                        server_password: "".to_string(),
+                    }),
+                    poi_info: None,
+                    db_info: None,
+                    website_info: None,
+                    run_on: None,
+                };
+                note_items(note, &title, &contents)
+            },
+        )
+        .filter(|i| filter.matches_type(&i.item_type))
+        .collect()
+}
+
+/// the title and the markdown contents of a note
+pub fn load_note(db_conn: &mut SqliteConnection, note: LinkedItemId) -> Option<(String, String)> {
+    use projectpadsql::schema::project_note::dsl as prj_note;
+    use projectpadsql::schema::server_note::dsl as srv_note;
+    match note {
+        LinkedItemId::ProjectNote(id) => prj_note::project_note
+            .filter(prj_note::id.eq(id))
+            .select((prj_note::title, prj_note::contents))
+            .first(db_conn)
+            .ok(),
+        LinkedItemId::ServerNote(id) => srv_note::server_note
+            .filter(srv_note::id.eq(id))
+            .select((srv_note::title, srv_note::contents))
+            .first(db_conn)
+            .ok(),
+        _ => None,
+    }
+}
+
+pub fn load_server(db_conn: &mut SqliteConnection, server_id: i32) -> Option<ItemOfInterest> {
+    filter_servers(db_conn, &ItemsFilter::default())
+        .into_iter()
+        .find(|i| i.linked_item == LinkedItemId::Server(server_id))
+}
+
+pub fn load_server_database(
+    db_conn: &mut SqliteConnection,
+    database_id: i32,
+) -> Option<ItemOfInterest> {
+    filter_server_databases(db_conn, &ItemsFilter::default())
+        .into_iter()
+        .find(|i| i.linked_item == LinkedItemId::ServerDatabase(database_id))
+}
+
+pub fn load_server_poi(db_conn: &mut SqliteConnection, poi_id: i32) -> Option<ItemOfInterest> {
+    filter_server_pois(db_conn, &ItemsFilter::default())
+        .into_iter()
+        .find(|i| i.linked_item == LinkedItemId::ServerPoi(poi_id))
+}
+
+/// the servers of the same project, environment and group as the
+/// given server (or of the whole environment), the server included
+pub fn load_fan_out_servers(
+    db_conn: &mut SqliteConnection,
+    server_id: i32,
+    whole_env: bool,
+) -> Vec<ItemOfInterest> {
+    let servers = filter_servers(db_conn, &ItemsFilter::default());
+    let reference = match servers
+        .iter()
+        .find(|s| s.linked_item == LinkedItemId::Server(server_id))
+    {
+        Some(s) => s.clone(),
+        None => return vec![],
+    };
+    servers
+        .into_iter()
+        .filter(|s| is_in_fan_out_group(&reference, s, whole_env))
         .collect()
 }
 
+fn is_in_fan_out_group(reference: &ItemOfInterest, item: &ItemOfInterest, whole_env: bool) -> bool {
+    let group = |i: &ItemOfInterest| i.server_info.as_ref().and_then(|s| s.server_group.clone());
+    item.project_name == reference.project_name
+        && item.env == reference.env
+        && (whole_env || group(item) == group(reference))
+}
+
 #[derive(Hash, PartialEq, Eq, EnumString, Display, Clone, Copy, PartialOrd, Ord, Debug)]
 pub enum ActionType {
     #[strum(serialize = "tail log")]
@@ -226,6 +1060,34 @@ pub enum ActionType {
     LessCfg,
     #[strum(serialize = "fetch cfg")]
     FetchCfg,
+    #[strum(serialize = "push cfg")]
+    PushCfg,
+    #[strum(serialize = "list bkp")]
+    ListBackups,
+    #[strum(serialize = "fetch bkp")]
+    FetchBackup,
+    #[strum(serialize = "psql db")]
+    PsqlDb,
+    #[strum(serialize = "mysql db")]
+    MysqlDb,
+    #[strum(serialize = "copy pass")]
+    CopyPassword,
+    #[strum(serialize = "copy user")]
+    CopyUsername,
+    #[strum(serialize = "open url")]
+    OpenUrl,
+    #[strum(serialize = "ssh as")]
+    SshAsUser,
+    #[strum(serialize = "run group")]
+    RunOnGroup,
+    #[strum(serialize = "fwd port")]
+    ForwardPort,
+    #[strum(serialize = "rdp")]
+    Rdp,
+    #[strum(serialize = "view note")]
+    ViewNote,
+    #[strum(serialize = "copy snip")]
+    CopySnippet,
 }
 
 #[derive(Hash, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Debug)]
@@ -240,94 +1102,278 @@ impl ExecutedAction {
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ActionHistoryEntry {
+    pub action: ExecutedAction,
+    /// the values the user gave for the placeholders of the
+    /// command, if any (see placeholders.rs)
+    pub placeholder_values: Vec<(String, String)>,
+    /// seconds since the epoch. None for entries written by older
+    /// versions of ppcli, which didn't store it
+    pub timestamp: Option<u64>,
+    /// the folder ppcli was started from
+    pub folder: Option<PathBuf>,
+}
+
+/// load all the actions, sorted with the most relevant first
+pub fn load_actions<T>(
+    conn: &mut SqliteConnection,
+    filter: &ItemsFilter,
+    ranked_items: &HashMap<ExecutedAction, T>,
+    sort: &[SortKey],
+) -> Vec<actions::Action>
+where
+    T: Ord,
+{
+    let mut items = filter_server_pois(conn, filter);
+    items.extend(filter_project_pois(conn, filter));
+    items.extend(filter_servers(conn, filter));
+    items.extend(filter_server_databases(conn, filter));
+    items.extend(filter_server_websites(conn, filter));
+    items.extend(filter_server_extra_users(conn, filter));
+    items.extend(filter_project_notes(conn, filter));
+    items.extend(filter_server_notes(conn, filter));
+    let mut actions: Vec<_> = items.into_iter().flat_map(actions::get_value).collect();
+    actions.sort_by(|a, b| {
+        sort.iter().fold(Ordering::Equal, |ordering, key| {
+            ordering.then_with(|| compare_actions(*key, a, b, ranked_items))
+        })
+    });
+    actions
+}
+
+fn compare_actions<T>(
+    sort_key: SortKey,
+    a: &actions::Action,
+    b: &actions::Action,
+    ranked_items: &HashMap<ExecutedAction, T>,
+) -> Ordering
+where
+    T: Ord,
+{
+    match sort_key {
+        SortKey::History => {
+            let rank = |action: &actions::Action| {
+                ranked_items.get(&ExecutedAction {
+                    item: action.item.linked_item,
+                    action_desc: action.desc,
+                })
+            };
+            rank(b).cmp(&rank(a))
+        }
+        SortKey::Project => b.item.project_name.cmp(&a.item.project_name),
+        SortKey::Env => b.item.env.cmp(&a.item.env),
+        SortKey::Server => b.item.server_info.cmp(&a.item.server_info),
+        SortKey::Type => b.item.item_type.cmp(&a.item.item_type),
+        SortKey::Text => b.item.item_text.cmp(&a.item.item_text),
+    }
+}
+
 pub fn load_items<T>(
     conn: &mut SqliteConnection,
-    display_mode: DisplayMode,
+    filter: &ItemsFilter,
+    layout: &RowLayout,
+    sort: &[SortKey],
     item_sender: &Sender<Arc<dyn SkimItem>>,
     ranked_items: &HashMap<ExecutedAction, T>,
 ) where
     T: Ord,
 {
-    let mut items = filter_server_pois(conn);
-    items.extend(filter_project_pois(conn));
-    items.extend(filter_servers(conn));
-    if items.is_empty() {
-        println!("No items to display. Keep in mind that ppcli will only display non RDP/non WWW servers, and point of interests");
+    let actions = load_actions(conn, filter, ranked_items, sort);
+    if actions.is_empty() {
+        println!("No items to display. Keep in mind that ppcli will only display servers, point of interests, databases and websites, and that --project, --env and --type (or a folder_project in ppcli.conf) restrict them");
         std::process::exit(0);
     }
-    // items.reverse();
-    let cols_spec = vec![7, 3, 4, 30, 25, 10];
-    let mut actions: Vec<_> = items.into_iter().flat_map(actions::get_value).collect();
-    actions.sort_by(|a, b| {
-        let a_rank = ranked_items.get(&ExecutedAction {
-            item: a.item.linked_item,
-            action_desc: a.desc,
-        });
-        let b_rank = ranked_items.get(&ExecutedAction {
-            item: b.item.linked_item,
-            action_desc: b.desc,
-        });
-        b_rank
-            .cmp(&a_rank)
-            .then(b.item.project_name.cmp(&a.item.project_name))
-            .then(b.item.server_info.cmp(&a.item.server_info))
-            .then(b.item.item_type.cmp(&a.item.item_type))
-            .then(b.item.item_text.cmp(&a.item.item_text))
-    });
+    let server_notes = load_server_notes(conn);
     for action in actions {
         let _ = item_sender.send(Arc::new(crate::MyItem {
-            display: render_row(&cols_spec, &action, display_mode),
+            display: render_row(layout, &action),
+            server_notes: action
+                .item
+                .server_info
+                .as_ref()
+                .and_then(|s| server_notes.get(&s.server_id).cloned()),
             inner: action,
         }));
     }
 }
 
-fn render_row(cols_spec: &[usize], action: &actions::Action, display_mode: DisplayMode) -> String {
+/// the notes of each server, rendered as plain text
+fn load_server_notes(db_conn: &mut SqliteConnection) -> HashMap<i32, Arc<String>> {
+    use projectpadsql::schema::server_note::dsl as srv_note;
+    let mut notes_by_server = HashMap::<i32, String>::new();
+    for (server_id, title, contents) in srv_note::server_note
+        .select((srv_note::server_id, srv_note::title, srv_note::contents))
+        .order(srv_note::title.asc())
+        .load::<(i32, String, String)>(db_conn)
+        .unwrap()
+    {
+        let notes = notes_by_server.entry(server_id).or_default();
+        notes.push_str(&format!(
+            "\n# {}\n{}",
+            title,
+            projectpadsql::notes::note_markdown_to_quick_preview(&contents).trim()
+        ));
+    }
+    notes_by_server
+        .into_iter()
+        .map(|(id, notes)| (id, Arc::new(notes)))
+        .collect()
+}
+
+/// the details of the item, to confirm we picked the right one
+pub fn render_preview(action: &actions::Action, server_notes: Option<&str>) -> String {
     let item = &action.item;
-    let mut col1 = item.project_name.clone();
-    col1.truncate(cols_spec[0]);
-    let col2 = item
-        .env
-        .as_ref()
-        .map(|env| display_env(env, display_mode))
-        .unwrap_or(if display_mode == DisplayMode::Color {
-            "-   "
-        } else {
-            "-  "
+    let mut lines = vec![];
+    let mut add_line = |label: &str, value: &str| {
+        if !value.is_empty() {
+            lines.push(format!("{:<10}{}", label, value));
+        }
+    };
+    add_line("Project:", &item.project_name);
+    add_line("Env:", item.env.as_ref().map(display_env).unwrap_or(""));
+    if let Some(server_info) = &item.server_info {
+        add_line("Server:", &server_info.server_desc);
+        add_line("IP:", &server_info.server_ip);
+        add_line("Username:", &server_info.server_username);
+        add_line(
+            "Via:",
+            &server_info
+                .ssh_jump_hosts
+                .iter()
+                .map(|h| h.spec.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+    add_line("Item:", item.poi_desc.as_deref().unwrap_or(""));
+    if let Some(poi_info) = &item.poi_info {
+        add_line("Path:", &poi_info.path.to_string_lossy());
+    }
+    add_line("Text:", &item.item_text);
+    if action.allowed_actions == [actions::AllowedAction::CopyToClipboard] {
+        // don't display secrets
+        add_line("Command:", "[hidden]");
+    } else {
+        add_line("Command:", &(action.get_string)(item));
+    }
+    let mut preview = lines.join("\n");
+    if let Some(notes) = server_notes {
+        preview.push_str("\n\nServer notes:");
+        preview.push_str(notes);
+    }
+    preview
+}
+
+/// how to display the rows of the list of actions
+pub struct RowLayout {
+    /// the visible columns, with their width
+    pub columns: Vec<(Column, usize)>,
+    pub display_mode: DisplayMode,
+    pub env_colors: EnvColors,
+}
+
+impl RowLayout {
+    pub fn new(
+        config: &CliConfig,
+        display_mode: DisplayMode,
+        terminal_width: Option<usize>,
+    ) -> RowLayout {
+        RowLayout {
+            columns: resolve_column_widths(&config.columns, terminal_width),
+            display_mode,
+            env_colors: config.env_colors.clone(),
+        }
+    }
+}
+
+const AUTO_COLUMN_DEFAULT_WIDTH: usize = 20;
+const AUTO_COLUMN_MIN_WIDTH: usize = 5;
+
+fn resolve_column_widths(
+    columns: &[ColumnSpec],
+    terminal_width: Option<usize>,
+) -> Vec<(Column, usize)> {
+    let auto_count = columns
+        .iter()
+        .filter(|c| c.width == ColumnWidth::Auto)
+        .count();
+    let fixed_width: usize = columns
+        .iter()
+        .map(|c| match c.width {
+            ColumnWidth::Fixed(w) => w,
+            ColumnWidth::Auto => 0,
+        })
+        .sum();
+    // the spaces between the columns, and the skim cursor
+    let used_width = fixed_width + columns.len() + 2;
+    let auto_width = match terminal_width {
+        Some(w) if auto_count > 0 => {
+            (w.saturating_sub(used_width) / auto_count).max(AUTO_COLUMN_MIN_WIDTH)
+        }
+        _ => AUTO_COLUMN_DEFAULT_WIDTH,
+    };
+    columns
+        .iter()
+        .map(|c| match c.width {
+            ColumnWidth::Fixed(w) => (c.column, w),
+            ColumnWidth::Auto => (c.column, auto_width),
         })
-        .to_string();
-    // col2.truncate(cols_spec[1]);
-    let mut col3 = render_type(&item.item_type).to_string();
-    col3.truncate(cols_spec[2]);
-    let mut col4 = item
-        .server_info
-        .as_ref()
-        .map(|si| si.server_desc.clone())
-        .unwrap_or_else(|| "-".to_string());
-    col4.truncate(cols_spec[3]);
-    let mut col5 = item
-        .poi_desc
-        .as_ref()
-        .cloned()
-        .unwrap_or_else(|| "".to_string());
-    col5.truncate(cols_spec[4]);
-    let mut col6 = action.desc.to_string();
-    col6.truncate(cols_spec[5]);
-    format!(
-        "{:<w1$} {:<w2$} {:<w3$} {:<w4$} {:<w5$}  {:<w6$}",
-        col1,
-        col2,
-        col3,
-        col4,
-        col5,
-        col6,
-        w1 = cols_spec[0],
-        w2 = cols_spec[1],
-        w3 = cols_spec[2],
-        w4 = cols_spec[3],
-        w5 = cols_spec[4],
-        w6 = cols_spec[5],
-    )
+        .collect()
+}
+
+pub fn render_row(layout: &RowLayout, action: &actions::Action) -> String {
+    let item = &action.item;
+    let mut row = String::new();
+    for (idx, (column, width)) in layout.columns.iter().enumerate() {
+        if idx > 0 {
+            row.push(' ');
+            if *column == Column::Action {
+                row.push(' ');
+            }
+        }
+        let contents = match column {
+            Column::Env => {
+                row.push_str(&render_env(item.env.as_ref(), *width, layout));
+                continue;
+            }
+            Column::Project => item.project_name.clone(),
+            Column::Type => render_type(&item.item_type).to_string(),
+            Column::Server => item
+                .server_info
+                .as_ref()
+                .map(|si| si.server_desc.clone())
+                .unwrap_or_else(|| "-".to_string()),
+            Column::Desc => item.poi_desc.clone().unwrap_or_default(),
+            Column::Action => action.desc.to_string(),
+        };
+        // truncate by chars, not bytes, we don't want to panic on accents
+        let truncated: String = contents.chars().take(*width).collect();
+        row.push_str(&format!("{:<w$}", truncated, w = width));
+    }
+    row
+}
+
+fn render_env(env: Option<&EnvironmentType>, width: usize, layout: &RowLayout) -> String {
+    let (label, color) = match env {
+        Some(e) => (
+            display_env(e),
+            match e {
+                EnvironmentType::EnvDevelopment => &layout.env_colors.dev,
+                EnvironmentType::EnvStage => &layout.env_colors.stg,
+                EnvironmentType::EnvUat => &layout.env_colors.uat,
+                EnvironmentType::EnvProd => &layout.env_colors.prd,
+            }
+            .as_str(),
+        ),
+        None => ("-", ""),
+    };
+    match (layout.display_mode, env) {
+        (DisplayMode::Color, Some(_)) => format!("\x1b[{}m❚{:<w$}\x1b[0m", color, label, w = width),
+        // one more char to align with the ❚ of the colored envs
+        (DisplayMode::Color, None) => format!("{:<w$}", label, w = width + 1),
+        (DisplayMode::Plain, _) => format!("{:<w$}", label, w = width),
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -336,20 +1382,16 @@ pub enum DisplayMode {
     Color,
 }
 
-fn display_env(env: &EnvironmentType, display_mode: DisplayMode) -> &'static str {
-    match (env, display_mode) {
-        (EnvironmentType::EnvDevelopment, DisplayMode::Color) => "\x1b[32m\x1b[1m❚DEV\x1b[0m",
-        (EnvironmentType::EnvDevelopment, DisplayMode::Plain) => "DEV",
-        (EnvironmentType::EnvStage, DisplayMode::Color) => "\x1b[34m\x1b[1m❚STG\x1b[0m",
-        (EnvironmentType::EnvStage, DisplayMode::Plain) => "STG",
-        (EnvironmentType::EnvUat, DisplayMode::Color) => "\x1b[33m\x1b[1m❚UAT\x1b[0m",
-        (EnvironmentType::EnvUat, DisplayMode::Plain) => "UAT",
-        (EnvironmentType::EnvProd, DisplayMode::Color) => "\x1b[31m\x1b[1m❚PRD\x1b[0m",
-        (EnvironmentType::EnvProd, DisplayMode::Plain) => "PRD",
+pub fn display_env(env: &EnvironmentType) -> &'static str {
+    match env {
+        EnvironmentType::EnvDevelopment => "DEV",
+        EnvironmentType::EnvStage => "STG",
+        EnvironmentType::EnvUat => "UAT",
+        EnvironmentType::EnvProd => "PRD",
     }
 }
 
-fn render_type(item_type: &ItemType) -> &'static str {
+pub fn render_type(item_type: &ItemType) -> &'static str {
     match item_type {
         ItemType::InterestItemType(InterestType::PoiCommandToRun) => "CMD",
         ItemType::InterestItemType(InterestType::PoiCommandTerminal) => "CMD",
@@ -362,5 +1404,124 @@ fn render_type(item_type: &ItemType) -> &'static str {
         ItemType::ServerItemType(ServerType::SrvHttpOrProxy) => "HTT",
         ItemType::ServerItemType(ServerType::SrvReporting) => "REP",
         ItemType::ServerItemType(ServerType::SrvMonitoring) => "MON",
+        ItemType::Database => "DB",
+        ItemType::Website => "WWW",
+        ItemType::Note => "NOTE",
+        ItemType::Snippet => "SNIP",
     }
 }
+
+#[test]
+fn resolve_column_widths_shares_the_terminal_width() {
+    let columns = vec![
+        ColumnSpec {
+            column: Column::Project,
+            width: ColumnWidth::Fixed(10),
+        },
+        ColumnSpec {
+            column: Column::Server,
+            width: ColumnWidth::Auto,
+        },
+        ColumnSpec {
+            column: Column::Desc,
+            width: ColumnWidth::Auto,
+        },
+    ];
+    assert_eq!(
+        vec![
+            (Column::Project, 10),
+            (Column::Server, 42),
+            (Column::Desc, 42)
+        ],
+        resolve_column_widths(&columns, Some(100))
+    );
+    assert_eq!(
+        vec![
+            (Column::Project, 10),
+            (Column::Server, 20),
+            (Column::Desc, 20)
+        ],
+        resolve_column_widths(&columns, None)
+    );
+}
+
+#[test]
+fn resolve_ssh_jump_hosts_multi_hop() {
+    let jump_hosts_info: HashMap<_, _> = vec![
+        (
+            1,
+            JumpHostInfo {
+                username: "bastion".to_string(),
+                ip: "bastion.example.com".to_string(),
+                access_type: ServerAccessType::SrvAccessSsh,
+                ssh_tunnel_through_server_id: None,
+                auth_key: None,
+            },
+        ),
+        (
+            2,
+            JumpHostInfo {
+                username: "".to_string(),
+                ip: "10.0.0.2:2222".to_string(),
+                access_type: ServerAccessType::SrvAccessSshTunnel,
+                ssh_tunnel_through_server_id: Some(1),
+                auth_key: None,
+            },
+        ),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        vec!["bastion@bastion.example.com", "10.0.0.2:2222"],
+        resolve_ssh_jump_hosts(
+            &jump_hosts_info,
+            ServerAccessType::SrvAccessSshTunnel,
+            Some(2)
+        )
+        .iter()
+        .map(|h| h.spec.as_str())
+        .collect::<Vec<_>>()
+    );
+    assert!(
+        resolve_ssh_jump_hosts(&jump_hosts_info, ServerAccessType::SrvAccessSsh, Some(2))
+            .is_empty()
+    );
+}
+
+#[test]
+fn debug_output_hides_the_secrets() {
+    let server_info = ServerInfo {
+        server_id: 1,
+        server_desc: "srv".to_string(),
+        server_username: "user".to_string(),
+        server_ip: "10.0.0.3".to_string(),
+        server_access_type: ServerAccessType::SrvAccessSsh,
+        ssh_jump_hosts: vec![],
+        ssh_auth_key: Some(SshAuthKey {
+            path: "/run/user/1000/ppcli/server-1".into(),
+            contents: b"private key".to_vec(),
+        }),
+        server_group: None,
+        server_password: "secret".to_string(),
+    };
+    let debug = format!("{:?}", server_info);
+    assert!(debug.contains("server-1"));
+    assert!(!debug.contains("secret"));
+    assert!(!debug.contains("contents: ["));
+}
+
+#[test]
+fn servers_sort_by_description() {
+    let server = |server_id, server_desc: &str| ServerInfo {
+        server_desc: server_desc.to_string(),
+        server_username: "user".to_string(),
+        server_ip: "10.0.0.3".to_string(),
+        server_id,
+        server_access_type: ServerAccessType::SrvAccessSsh,
+        ssh_jump_hosts: vec![],
+        ssh_auth_key: None,
+        server_group: None,
+        server_password: "".to_string(),
+    };
+    assert!(server(2, "app") < server(1, "db"));
+}
@@ -0,0 +1,172 @@
+// the database password is normally read from the OS keyring, where
+// the projectpad GUI app stored it. Headless servers and ssh sessions
+// usually have no keyring, so we have fallbacks, in that order:
+// - PROJECTPAD_DB_PASSWORD_FILE: a file containing the password
+// - password_command in ppcli.conf: a command printing the password
+// - the OS keyring
+// - the password cached by a previous ppcli run, if password_cache_secs
+//   is set in ppcli.conf. The password is kept in memory by a background
+//   ppcli process, which hands it out through a unix socket readable
+//   only by the user, and exits after that delay.
+// - prompting for the password on the terminal
+use crate::authkeys;
+use crate::config::CliConfig;
+use crate::prompt;
+use crate::secretservice;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const PASSWORD_FILE_ENV_VAR: &str = "PROJECTPAD_DB_PASSWORD_FILE";
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PasswordSource {
+    File,
+    Command,
+    Keyring,
+    Cache,
+    Prompt,
+}
+
+pub struct DbPassword {
+    pub password: String,
+    pub source: PasswordSource,
+}
+
+pub fn get_db_password(config: &CliConfig) -> Result<DbPassword, Box<dyn Error>> {
+    if let Ok(file_path) = std::env::var(PASSWORD_FILE_ENV_VAR) {
+        let contents = std::fs::read_to_string(&file_path)
+            .map_err(|e| format!("Error reading {}: {}", file_path, e))?;
+        return Ok(DbPassword {
+            password: first_line(&contents),
+            source: PasswordSource::File,
+        });
+    }
+    if let Some(command) = &config.password_command {
+        return Ok(DbPassword {
+            password: run_password_command(command)?,
+            source: PasswordSource::Command,
+        });
+    }
+    // errors mean there is no keyring, for instance in a ssh session
+    if let Ok(Some(password)) = secretservice::get_keyring_pass() {
+        return Ok(DbPassword {
+            password,
+            source: PasswordSource::Keyring,
+        });
+    }
+    if config.password_cache_secs > 0 {
+        if let Some(password) = read_cached_password() {
+            return Ok(DbPassword {
+                password,
+                source: PasswordSource::Cache,
+            });
+        }
+    }
+    match prompt::ask_password_on_tty("projectpad database password: ") {
+        Some(password) if !password.is_empty() => Ok(DbPassword {
+            password,
+            source: PasswordSource::Prompt,
+        }),
+        _ => Err(format!(
+            "no password in the OS keyring, {} or password_command in ppcli.conf, and none was typed",
+            PASSWORD_FILE_ENV_VAR
+        )
+        .into()),
+    }
+}
+
+/// to call once the password unlocked the database. Caches the
+/// password if the user had to type it.
+pub fn password_validated(db_password: &DbPassword, config: &CliConfig) {
+    if db_password.source == PasswordSource::Prompt && config.password_cache_secs > 0 {
+        if let Err(e) = start_cache_agent(&db_password.password, config.password_cache_secs) {
+            eprintln!("Error caching the database password: {}", e);
+        }
+    }
+}
+
+fn first_line(contents: &str) -> String {
+    contents.lines().next().unwrap_or("").to_string()
+}
+
+fn run_password_command(command: &str) -> Result<String, Box<dyn Error>> {
+    let cl_elts = shell_words::split(command)?;
+    if cl_elts.is_empty() {
+        return Err("password_command is empty".into());
+    }
+    // stdin and stderr are inherited, the command may prompt the user (gpg...)
+    let output = Command::new(&cl_elts[0])
+        .args(cl_elts.iter().skip(1))
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Error running the password command {}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!("The password command {} failed: {}", command, output.status).into());
+    }
+    Ok(first_line(&String::from_utf8(output.stdout)?))
+}
+
+fn agent_socket_path() -> PathBuf {
+    let mut path = authkeys::runtime_folder();
+    path.push("password-agent.sock");
+    path
+}
+
+fn read_cached_password() -> Option<String> {
+    let mut stream = UnixStream::connect(agent_socket_path()).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(1))).ok()?;
+    let mut password = String::new();
+    stream.read_to_string(&mut password).ok()?;
+    Some(password).filter(|p| !p.is_empty())
+}
+
+fn start_cache_agent(password: &str, cache_secs: u64) -> Result<(), Box<dyn Error>> {
+    let mut child = unsafe {
+        Command::new(std::env::current_exe()?)
+            .arg("--password-agent")
+            .arg(cache_secs.to_string())
+            .stdin(Stdio::piped())
+            // in shell integration mode, the shell waits until our stdout
+            // is closed: the agent must not inherit it
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            // don't get killed with the terminal foreground process group
+            .pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            })
+            .spawn()?
+    };
+    child.stdin.take().unwrap().write_all(password.as_bytes())?;
+    Ok(())
+}
+
+/// the agent process: reads the password from stdin, and hands
+/// it out through the socket until the delay expires.
+pub fn run_agent(cache_secs: u64) -> Result<(), Box<dyn Error>> {
+    let mut password = String::new();
+    std::io::stdin().read_to_string(&mut password)?;
+    let folder = authkeys::runtime_folder();
+    authkeys::create_private_folder(&folder)?;
+    let socket_path = agent_socket_path();
+    // a previous agent may have died without cleaning up
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path)?;
+    std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))?;
+    let expired_socket_path = socket_path.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_secs(cache_secs));
+        let _ = std::fs::remove_file(&expired_socket_path);
+        std::process::exit(0);
+    });
+    for mut stream in listener.incoming().flatten() {
+        let _ = stream.write_all(password.as_bytes());
+    }
+    Ok(())
+}
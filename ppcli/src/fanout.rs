@@ -0,0 +1,199 @@
+// run a command on all the servers of a group (or of an environment)
+// in parallel, prefixing each output line with the server name, and
+// printing a summary of the exit codes at the end.
+use crate::actions;
+use crate::authkeys;
+use crate::config::CliConfig;
+use crate::database::{self, DisplayMode, ItemOfInterest};
+use crate::placeholders;
+use crate::prod_guard;
+use diesel::sqlite::SqliteConnection;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+/// returns the exit code for the process
+pub fn run(
+    conn: &mut SqliteConnection,
+    poi_id: Option<i32>,
+    server_id: Option<i32>,
+    whole_env: bool,
+    command: &[String],
+    cli_config: &CliConfig,
+    allow_prod: bool,
+) -> i32 {
+    let (server_id, command) = match (poi_id, server_id) {
+        (Some(poi_id), _) => match database::load_server_poi(conn, poi_id) {
+            Some(poi) => (poi.server_info.unwrap().server_id, poi.item_text),
+            None => {
+                eprintln!("No server point of interest with id {}", poi_id);
+                return 1;
+            }
+        },
+        // asking here rather than with a placeholder in the action: the
+        // command would be split into words and joined back, losing its quoting
+        (None, Some(server_id)) if command.is_empty() => (server_id, "{{ask:command}}".to_string()),
+        // like ssh, the words after -- make the remote command line
+        (None, Some(server_id)) => (server_id, command.join(" ")),
+        (None, None) => {
+            eprintln!("Either --poi or --server is required");
+            return 1;
+        }
+    };
+    let command = match placeholders::fill_placeholders(&command, &mut vec![], &[]) {
+        Some(c) if !c.trim().is_empty() => c,
+        _ => {
+            eprintln!("No command to run");
+            return 1;
+        }
+    };
+    let servers: Vec<_> = database::load_fan_out_servers(conn, server_id, whole_env)
+        .into_iter()
+        .filter(actions::is_ssh_access)
+        .collect();
+    if servers.is_empty() {
+        eprintln!("No ssh server to run the command on");
+        return 1;
+    }
+    let group_desc = group_desc(&servers[0], whole_env);
+    if !prod_guard::can_fan_out(
+        &servers,
+        &group_desc,
+        cli_config,
+        allow_prod,
+        DisplayMode::Plain,
+    ) {
+        return 1;
+    }
+    let targets: Vec<_> = servers
+        .iter()
+        .filter_map(|s| {
+            actions::get_ssh_batch_command(s, &command).map(|cmd| (server_name(s), cmd))
+        })
+        .collect();
+    println!(
+        "Running {} on {} servers of {}...",
+        command,
+        targets.len(),
+        group_desc
+    );
+    for server in &servers {
+        authkeys::materialize_item_auth_key(server);
+    }
+    let results = run_parallel(targets);
+    for server in &servers {
+        authkeys::remove_item_auth_key(server);
+    }
+    print_summary(&results)
+}
+
+/// run commands given on the command-line in parallel, for instance
+/// several actions selected together in the list (see batch.rs)
+pub fn run_named(names: &[String], commands: &[String]) -> i32 {
+    if names.len() != commands.len() {
+        eprintln!("Got {} names for {} commands", names.len(), commands.len());
+        return 1;
+    }
+    let targets = names
+        .iter()
+        .cloned()
+        .zip(commands.iter().cloned())
+        .collect();
+    print_summary(&run_parallel(targets))
+}
+
+/// returns the exit code for the process
+fn print_summary(results: &[(String, Option<i32>)]) -> i32 {
+    println!();
+    for (name, code) in results {
+        match code {
+            Some(0) => println!("{}: ok", name),
+            Some(c) => println!("{}: exit code {}", name, c),
+            None => println!("{}: failed", name),
+        }
+    }
+    if results.iter().all(|(_, code)| *code == Some(0)) {
+        0
+    } else {
+        1
+    }
+}
+
+fn server_name(item: &ItemOfInterest) -> String {
+    let server_info = item.server_info.as_ref().unwrap();
+    if server_info.server_desc.is_empty() {
+        server_info.server_ip.clone()
+    } else {
+        server_info.server_desc.clone()
+    }
+}
+
+fn group_desc(item: &ItemOfInterest, whole_env: bool) -> String {
+    let env = item.env.as_ref().map(database::display_env).unwrap_or("");
+    match item
+        .server_info
+        .as_ref()
+        .and_then(|s| s.server_group.as_ref())
+    {
+        Some(group) if !whole_env => format!("{} {} {}", item.project_name, env, group),
+        _ => format!("{} {}", item.project_name, env),
+    }
+}
+
+/// run the commands in parallel, returns the exit code for each of
+/// them, None if it couldn't be run or was killed
+fn run_parallel(targets: Vec<(String, String)>) -> Vec<(String, Option<i32>)> {
+    let width = targets.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+    let handles: Vec<_> = targets
+        .into_iter()
+        .map(|(name, command)| {
+            std::thread::spawn(move || {
+                let code = run_prefixed(&format!("[{:<w$}] ", name, w = width), &command);
+                (name, code)
+            })
+        })
+        .collect();
+    handles.into_iter().map(|h| h.join().unwrap()).collect()
+}
+
+fn run_prefixed(prefix: &str, command: &str) -> Option<i32> {
+    let cl_elts = shell_words::split(command).ok()?;
+    let mut child = Command::new(cl_elts.first()?)
+        .args(cl_elts.iter().skip(1))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eprintln!("{}Error launching process: {}", prefix, e))
+        .ok()?;
+    let stderr = child.stderr.take().unwrap();
+    let stderr_prefix = prefix.to_string();
+    let stderr_thread = std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            eprintln!("{}{}", stderr_prefix, line);
+        }
+    });
+    for line in BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map_while(Result::ok)
+    {
+        println!("{}{}", prefix, line);
+    }
+    let _ = stderr_thread.join();
+    child.wait().ok()?.code()
+}
+
+#[test]
+fn run_parallel_collects_exit_codes() {
+    assert_eq!(
+        vec![
+            ("a".to_string(), Some(0)),
+            ("b".to_string(), Some(3)),
+            ("c".to_string(), None)
+        ],
+        run_parallel(vec![
+            ("a".to_string(), "true".to_string()),
+            ("b".to_string(), "sh -c 'exit 3'".to_string()),
+            ("c".to_string(), "/nonexistent/command".to_string()),
+        ])
+    );
+}
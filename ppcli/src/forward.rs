@@ -0,0 +1,52 @@
+// forward a local port to a database on a server, so that database tools
+// (DBeaver...) on this machine can connect to it. The local port is picked
+// right before starting ssh, not when the list is displayed: another
+// program may take it in the meantime.
+use crate::actions;
+use crate::authkeys;
+use crate::database::{self, ItemOfInterest, ItemType};
+use diesel::sqlite::SqliteConnection;
+use projectpadsql::models::ServerType;
+use std::net::TcpListener;
+
+/// returns the exit code for the process
+pub fn run(conn: &mut SqliteConnection, database_id: Option<i32>, server_id: Option<i32>) -> i32 {
+    let item = match (database_id, server_id) {
+        (Some(database_id), _) => database::load_server_database(conn, database_id),
+        (None, Some(server_id)) => database::load_server(conn, server_id)
+            .filter(|i| i.item_type == ItemType::ServerItemType(ServerType::SrvDatabase)),
+        (None, None) => None,
+    };
+    let item = match item.filter(actions::is_ssh_access) {
+        Some(item) => item,
+        None => {
+            eprintln!("No database reachable through ssh with that id");
+            return 1;
+        }
+    };
+    let local_port = free_local_port(actions::db_default_port(&item));
+    match actions::forward_port_command(&item, local_port) {
+        Some((connection_string, ssh_command)) => {
+            println!("Forwarding {}, ctrl-c to stop", connection_string);
+            run_ssh(&item, &ssh_command)
+        }
+        None => {
+            eprintln!("Cannot build the ssh command for the server");
+            1
+        }
+    }
+}
+
+/// the default port of the database if it's free, otherwise the next free one
+fn free_local_port(preferred: u16) -> u16 {
+    (preferred..=u16::MAX)
+        .find(|port| TcpListener::bind(("127.0.0.1", *port)).is_ok())
+        .unwrap_or(preferred)
+}
+
+fn run_ssh(item: &ItemOfInterest, ssh_command: &str) -> i32 {
+    authkeys::materialize_item_auth_key(item);
+    let status = crate::run_command(ssh_command, &dirs::home_dir().unwrap());
+    authkeys::remove_item_auth_key(item);
+    status.and_then(|s| s.code()).unwrap_or(1)
+}
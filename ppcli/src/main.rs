@@ -1,4 +1,5 @@
-use crate::database::ExecutedAction;
+use crate::config::PasteMethod;
+use crate::database::{ActionHistoryEntry, ExecutedAction};
 #[cfg(test)]
 use crate::database::{ActionType, LinkedItemId};
 use database::DisplayMode;
@@ -8,22 +9,37 @@ use skim::prelude::*;
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::env;
-use std::io::Write;
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus};
 use std::sync::mpsc;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 mod actions;
+mod authkeys;
 mod autoupgrade;
+mod batch;
+mod clipboard;
 pub mod config;
 mod database;
+mod dbpassword;
+mod fanout;
+mod forward;
+mod noninteractive;
+mod notes;
+mod placeholders;
+mod prod_guard;
+mod prompt;
+mod push;
+mod rdp;
 #[cfg_attr(target_os = "linux", path = "secretservice_linux.rs")]
 #[cfg_attr(not(target_os = "linux"), path = "secretservice_generic.rs")]
 mod secretservice;
 
 const ZSH_FUNCTION: &str = include_str!("../shell/integration.zsh");
+const BASH_FUNCTION: &str = include_str!("../shell/integration.bash");
+const FISH_FUNCTION: &str = include_str!("../shell/integration.fish");
 
 const MIN_SUPPORTED_DB_SCHEMA_VERSION: i32 = 21;
 const MAX_SUPPORTED_DB_SCHEMA_VERSION: i32 = 22;
@@ -34,17 +50,37 @@ struct Options {
     /// Upgrade ppcli
     #[structopt(long)]
     upgrade: bool,
+    /// Upgrade ppcli from a downloaded release archive, with its .sha256 file next to it
+    #[structopt(long, parse(from_os_str))]
+    upgrade_from: Option<PathBuf>,
     /// Disable color display
     #[structopt(long="no-color", parse(from_flag = display_from_no_color))]
     display_mode: DisplayMode,
+    /// Allow running command POIs on production servers, when
+    /// prod_commands_require_allow_prod is enabled in ppcli.conf
+    #[structopt(long)]
+    allow_prod: bool,
+    #[structopt(flatten)]
+    filter: noninteractive::ItemFilter,
     /// Disable the new version check
     #[structopt(long = "no-upgrade-check", parse(from_flag = std::ops::Not::not))]
     upgrade_check: bool,
     #[structopt(long = "shell-integration", hidden = true)]
     shell_integration_mode: bool,
+    /// run the agent caching the database password, for that many seconds
+    #[structopt(long, hidden = true)]
+    password_agent: Option<u64>,
+    /// remove the ssh keys listed on stdin after that many seconds
+    #[structopt(long, hidden = true)]
+    remove_auth_keys: Option<u64>,
+    /// clear the clipboard after that many seconds, if it didn't change
+    #[structopt(long, hidden = true)]
+    clear_clipboard: Option<u64>,
     /// Print to stdout the function for a given shell
     #[structopt(long, default_value = "none")]
     print_shell_function: Shell,
+    #[structopt(subcommand)]
+    subcommand: Option<noninteractive::SubCommand>,
 }
 
 arg_enum! {
@@ -52,12 +88,15 @@ arg_enum! {
     enum Shell {
         None,
         Zsh,
+        Bash,
+        Fish,
     }
 }
 
 pub struct MyItem {
     display: String,
     inner: actions::Action,
+    server_notes: Option<Arc<String>>,
 }
 
 fn remove_ansi_escapes(input: &str) -> Cow<str> {
@@ -80,9 +119,22 @@ impl SkimItem for MyItem {
     }
 
     fn preview(&self, _context: PreviewContext) -> ItemPreview {
-        ItemPreview::Text(
-            "[enter] run, [alt-enter] paste to prompt, [ctl-y] copy to clipboard, [ctl-n/p] history".to_string(),
-        )
+        let details = database::render_preview(
+            &self.inner,
+            self.server_notes.as_deref().map(String::as_str),
+        );
+        let text = format!(
+            "[enter] run, [alt-enter] paste to prompt, [ctl-y] copy to clipboard, [tab] select several, [ctl-n/p] history\n\n{}",
+            details
+        );
+        match actions::get_preview_command(&self.inner.item) {
+            Some(command) => ItemPreview::Command(format!(
+                "printf '%s\\n' {}; {} 2>&1",
+                shell_words::quote(&text),
+                command
+            )),
+            None => ItemPreview::Text(text),
+        }
     }
 }
 
@@ -124,8 +176,12 @@ enum UpgradeAvailableData {
 
 pub fn main() {
     let flag_options = Options::from_args();
-    if flag_options.upgrade {
-        match autoupgrade::try_upgrade() {
+    if flag_options.upgrade || flag_options.upgrade_from.is_some() {
+        let upgrade_result = match &flag_options.upgrade_from {
+            Some(archive_path) => autoupgrade::upgrade_from(archive_path),
+            None => autoupgrade::try_upgrade(),
+        };
+        match upgrade_result {
             Ok(()) => {
                 // don't bug the user about this upgrade for
                 // some time now (even if the user rejected the upgrade)
@@ -138,13 +194,53 @@ pub fn main() {
         }
         std::process::exit(0);
     }
-    if flag_options.print_shell_function == Shell::Zsh {
-        println!("\n{}", ZSH_FUNCTION);
+    let shell_function = match flag_options.print_shell_function {
+        Shell::None => None,
+        Shell::Zsh => Some(ZSH_FUNCTION),
+        Shell::Bash => Some(BASH_FUNCTION),
+        Shell::Fish => Some(FISH_FUNCTION),
+    };
+    if let Some(shell_function) = shell_function {
+        println!("\n{}", shell_function);
+        std::process::exit(0);
+    }
+    if let Some(noninteractive::SubCommand::Parallel { names, commands }) = &flag_options.subcommand
+    {
+        // no need to unlock the database for that one
+        std::process::exit(fanout::run_named(names, commands));
+    }
+    if let Some(lifetime_secs) = flag_options.remove_auth_keys {
+        if let Err(e) = authkeys::run_removal_agent(lifetime_secs) {
+            eprintln!("Error removing the ssh keys: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+    if let Some(cache_secs) = flag_options.password_agent {
+        if let Err(e) = dbpassword::run_agent(cache_secs) {
+            eprintln!("Error in the password agent: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+    let cli_config = config::read_cli_config().unwrap_or_else(|e| {
+        eprintln!(
+            "Error reading the ppcli configuration, using the defaults: {}",
+            e
+        );
+        config::CliConfig::default()
+    });
+    if let Some(clear_secs) = flag_options.clear_clipboard {
+        if let Err(e) = clipboard::run_clear_agent(clear_secs, &cli_config) {
+            eprintln!("Error clearing the clipboard: {}", e);
+            std::process::exit(1);
+        }
         std::process::exit(0);
     }
+
     let db_pass = ok_or_exit!(
-        secretservice::get_keyring_pass().and_then(|r| r.ok_or_else(|| "no matching credentials".into())),
-        "Cannot find the database password in the OS keyring, aborting: did you run the projectpad GUI app to create a database first? {}",
+        dbpassword::get_db_password(&cli_config),
+        "Cannot get the database password, aborting: did you run the projectpad GUI app to create a database first? {}",
         1
     );
 
@@ -162,10 +258,11 @@ pub fn main() {
     );
 
     ok_or_exit!(
-        projectpadsql::try_unlock_db(&mut conn, &db_pass),
+        projectpadsql::try_unlock_db(&mut conn, &db_pass.password),
         "Failed unlocking the database with the password, aborting. {}",
         4
     );
+    dbpassword::password_validated(&db_pass, &cli_config);
 
     ok_or_exit!(
         check_db_version(&mut conn),
@@ -173,6 +270,23 @@ pub fn main() {
         5
     );
 
+    let filter = flag_options.filter.items_filter(&cli_config);
+    if let Some(subcommand) = flag_options.subcommand {
+        // no upgrade check in non-interactive mode, we don't want to
+        // prompt the user in the middle of a script
+        let history_entries = config::read_action_history().unwrap_or_else(|_| vec![]);
+        let ranked_items = get_ranked_items(&history_entries, &cli_config);
+        std::process::exit(noninteractive::run(
+            &mut conn,
+            subcommand,
+            &filter,
+            &ranked_items,
+            &history_entries,
+            &cli_config,
+            flag_options.allow_prod,
+        ));
+    }
+
     // start a thread to, if we didn't check for 7 days, check whether there is
     // a new version of ppcli available (in a thread not to block the GUI).
     // We write to a channel and check the contents of the channel at the end
@@ -206,14 +320,14 @@ pub fn main() {
     }
 
     let history_strs = config::read_string_history().unwrap_or_else(|_| vec![]);
-    let history_executed_actions = config::read_action_history().unwrap_or_else(|_| vec![]);
+    let history_entries = config::read_action_history().unwrap_or_else(|_| vec![]);
     let options = SkimOptionsBuilder::default()
         .bind(vec!["ctrl-p:previous-history", "ctrl-n:next-history"])
         .expect(Some("ctrl-y,alt-enter".to_string()))
         // .height(Some("50%"))
-        // .multi(true)
+        .multi(true)
         .preview(Some("")) // preview should be specified to enable preview window
-        .preview_window(Some("up:2"))
+        .preview_window(Some("up:40%:wrap"))
         // .layout("reverse-list")
         // .reverse(true)
         .query_history(&history_strs)
@@ -224,34 +338,120 @@ pub fn main() {
 
     let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
 
-    let display_mode = flag_options.display_mode;
-    let ranked_items = get_ranked_items(&history_executed_actions);
+    let display_mode = if cli_config.color {
+        flag_options.display_mode
+    } else {
+        DisplayMode::Plain
+    };
+    let ranked_items = get_ranked_items(&history_entries, &cli_config);
+    let layout = database::RowLayout::new(&cli_config, display_mode, terminal_width());
+    let sort = cli_config.sort.clone();
     std::thread::spawn(move || {
-        database::load_items(&mut conn, display_mode, &tx_item, &ranked_items)
+        database::load_items(&mut conn, &filter, &layout, &sort, &tx_item, &ranked_items)
     });
 
     let (selected_items, query, accept_key) = Skim::run_with(&options, Some(rx_item))
         .map(|out| (out.selected_items, out.query, out.final_key))
         .unwrap_or_else(|| (Vec::new(), "".to_string(), Key::Enter));
 
-    if let Some(item) = selected_items.get(0) {
-        // this pattern from the skim apidocs for SkimItem, and also
-        // https://stackoverflow.com/a/26128001/516188
-        let myitem = (**item).as_any().downcast_ref::<MyItem>().unwrap();
-
-        let item_of_interest = &myitem.inner.item;
+    // this pattern from the skim apidocs for SkimItem, and also
+    // https://stackoverflow.com/a/26128001/516188
+    let selected: Vec<&MyItem> = selected_items
+        .iter()
+        .map(|item| (**item).as_any().downcast_ref::<MyItem>().unwrap())
+        .collect();
+    if !selected.is_empty() {
         if !query.is_empty() {
             config::write_string_history(&history_strs, &query, 100).unwrap();
         }
-        config::write_actions_history(
-            &history_executed_actions,
-            ExecutedAction::new(item_of_interest.linked_item, myitem.inner.desc),
-            100,
-        )
-        .unwrap();
 
-        let action = &myitem.inner;
-        let action_str = &(action.get_string)(&action.item);
+        let mut history_entries = history_entries;
+        let mut new_history_entries = vec![];
+        let mut commands = vec![];
+        for myitem in &selected {
+            let action = &myitem.inner;
+            let executed_action = ExecutedAction::new(action.item.linked_item, action.desc);
+            let raw_action_str = actions::forward_allow_prod(
+                action,
+                (action.get_string)(&action.item),
+                flag_options.allow_prod,
+            );
+            let (action_string, command_folder, placeholder_values) =
+                if action.allowed_actions == [actions::AllowedAction::CopyToClipboard] {
+                    // secrets, no placeholders there
+                    (raw_action_str.to_string(), None, vec![])
+                } else {
+                    some_or_exit!(
+                        placeholders::fill_action_placeholders(
+                            &raw_action_str,
+                            run_command_folder(action),
+                            &placeholders::previous_values(&history_entries, executed_action),
+                        ),
+                        "Cancelled.",
+                        1
+                    )
+                };
+            new_history_entries.push(ActionHistoryEntry {
+                action: executed_action,
+                placeholder_values,
+                timestamp: Some(now_timestamp()),
+                folder: env::current_dir().ok(),
+            });
+            commands.push((action, action_string, command_folder));
+        }
+        let selected_actions: Vec<&actions::Action> = commands.iter().map(|(a, _, _)| *a).collect();
+
+        let secrets: Vec<_> = selected_actions
+            .iter()
+            .map(|a| a.allowed_actions == [actions::AllowedAction::CopyToClipboard])
+            .collect();
+        let accept_key = match accept_key_for_secrets(&secrets, accept_key) {
+            Some(k) => k,
+            None => {
+                eprintln!("Passwords can only be copied, select them without other actions.");
+                Key::ESC
+            }
+        };
+        let accept_key = if accept_key == Key::Enter
+            && !selected_actions
+                .iter()
+                .all(|a| prod_guard::can_run(a, &cli_config, flag_options.allow_prod, display_mode))
+        {
+            // the user didn't confirm, nothing to do
+            Key::ESC
+        } else {
+            accept_key
+        };
+        // only remember the actions which went through
+        if matches!(accept_key, Key::Enter | Key::AltEnter | Key::Ctrl('y')) {
+            for history_entry in new_history_entries {
+                config::write_actions_history(
+                    &history_entries,
+                    history_entry.clone(),
+                    ACTION_HISTORY_LIMIT,
+                )
+                .unwrap();
+                history_entries.push(history_entry);
+            }
+        }
+        let (action_string, command_folder, detached) = match (&commands[..], &accept_key) {
+            ([(_, command, folder)], _) => (command.clone(), folder.clone(), false),
+            (_, Key::Enter) => {
+                let batch =
+                    ok_or_exit!(batch::prepare(&commands, env::var("TMUX").is_ok()), "{}", 1);
+                (batch.command, None, batch.detached)
+            }
+            // several commands pasted to the prompt: run them one after the other
+            (_, Key::AltEnter) => (join_commands(&commands, "; "), None, false),
+            _ => (join_commands(&commands, "\n"), None, false),
+        };
+        let action_str: &str = &action_string;
+
+        // the ssh keys must be on disk for the commands to work, even if
+        // the commands will only be run later by the user
+        for action in &selected_actions {
+            authkeys::materialize_item_auth_key(&action.item);
+        }
         let upgrade_url = if flag_options.shell_integration_mode {
             // in shell integration mode, we check for upgrades before handling
             // the command, because we just print out the command, the shell
@@ -262,27 +462,70 @@ pub fn main() {
         } else {
             Cow::Borrowed("")
         };
+        let run_by_ppcli = accept_key == Key::Enter && !flag_options.shell_integration_mode;
         match accept_key {
-            Key::Ctrl('y') if flag_options.shell_integration_mode => println!("C\x00{}\x00\x00{}", action_str, upgrade_url),
-            Key::Ctrl('y') => copy_command_to_clipboard(action_str),
-            Key::AltEnter if flag_options.shell_integration_mode => println!("P\x00{}\x00\x00{}", action_str, upgrade_url),
+            Key::Ctrl('y') => {
+                let secret = selected_actions
+                    .iter()
+                    .any(|a| a.allowed_actions == [actions::AllowedAction::CopyToClipboard]);
+                clipboard::copy(action_str, &cli_config, secret);
+                // don't hand passwords to the shell
+                if flag_options.shell_integration_mode && !secret {
+                    // functions installed by older ppcli versions copy
+                    // the command themselves
+                    println!(
+                        "{}",
+                        shell_integration_output(
+                            ShellIntegrationOp::Copy,
+                            action_str,
+                            "",
+                            &upgrade_url
+                        )
+                    )
+                }
+            }
+            Key::AltEnter if flag_options.shell_integration_mode => println!(
+                "{}",
+                shell_integration_output(ShellIntegrationOp::Paste, action_str, "", &upgrade_url)
+            ),
             Key::AltEnter =>
             // copy to command-line if run is not allowed for that action
                     // if !val_action.allowed_actions.contains(&AllowedAction::Run) =>
             {
-                write_command_line_to_terminal(action_str)
+                write_command_line_to_terminal(action_str, cli_config.paste_method)
             }
             Key::Enter if flag_options.shell_integration_mode => println!(
-                "R\x00{}\x00{}\x00{}", action_str, &run_command_folder(action)
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "".to_string()), upgrade_url),
-            Key::Enter => run_command(
-                action_str,
-                &run_command_folder(action)
-                    .unwrap_or_else(|| dirs::home_dir().unwrap()),
+                "{}",
+                shell_integration_output(
+                    ShellIntegrationOp::Run,
+                    action_str,
+                    &command_folder
+                        .as_ref()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "".to_string()),
+                    &upgrade_url
+                )
             ),
+            Key::Enter => {
+                run_command(
+                    action_str,
+                    &command_folder.unwrap_or_else(|| dirs::home_dir().unwrap()),
+                );
+                // the tmux panes may still need the keys
+                if !detached {
+                    for action in &selected_actions {
+                        authkeys::remove_item_auth_key(&action.item);
+                    }
+                }
+            }
             _ => {}
         }
+        if !run_by_ppcli || detached {
+            authkeys::schedule_auth_keys_removal(
+                selected_actions.iter().map(|a| &a.item),
+                cli_config.auth_key_lifetime_secs,
+            );
+        }
     }
     if !flag_options.shell_integration_mode {
         if let Some(download_url) = handle_upgrade_info_and_get_download_url(&has_upgrade_rx) {
@@ -293,6 +536,54 @@ pub fn main() {
     }
 }
 
+/// actions which can only be copied (for instance passwords) are copied
+/// to the clipboard, whatever the key: pasted to the prompt, they would
+/// end up in the shell history. Returns None for selections mixing
+/// them with other actions.
+fn accept_key_for_secrets(secrets: &[bool], accept_key: Key) -> Option<Key> {
+    let secret_count = secrets.iter().filter(|s| **s).count();
+    match accept_key {
+        _ if secret_count == 0 => Some(accept_key),
+        _ if secret_count < secrets.len() => None,
+        Key::Enter | Key::AltEnter => Some(Key::Ctrl('y')),
+        _ => Some(accept_key),
+    }
+}
+
+fn join_commands(commands: &[(&actions::Action, String, Option<PathBuf>)], sep: &str) -> String {
+    commands
+        .iter()
+        .map(|(_, command, _)| command.as_str())
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+enum ShellIntegrationOp {
+    Run,
+    Paste,
+    Copy,
+}
+
+/// in shell integration mode, we give the shell the info it needs to
+/// handle the action, the shell scripts are in the shell/ folder.
+/// The fields are separated by NUL: the operation (R for run, P for paste
+/// to the prompt, C when the command was copied to the clipboard), the command, the folder
+/// in which to run the command (only for run) and the URL of an upgrade
+/// of ppcli, if one is available.
+fn shell_integration_output(
+    op: ShellIntegrationOp,
+    command: &str,
+    folder: &str,
+    upgrade_url: &str,
+) -> String {
+    let op_code = match op {
+        ShellIntegrationOp::Run => "R",
+        ShellIntegrationOp::Paste => "P",
+        ShellIntegrationOp::Copy => "C",
+    };
+    [op_code, command, folder, upgrade_url].join("\x00")
+}
+
 fn handle_upgrade_info_and_get_download_url(
     has_upgrade_rx: &mpsc::Receiver<UpgradeAvailableData>,
 ) -> Option<String> {
@@ -309,23 +600,66 @@ fn handle_upgrade_info_and_get_download_url(
     }
 }
 
-/// we want that between two with the same count the latest wins.
-/// so we rank by a tuple: first the number of uses of that command,
-/// second the index of the last use of that command.
+/// with time decay, we can afford to keep a long history
+const ACTION_HISTORY_LIMIT: usize = 1000;
+
+fn now_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// the rank of each action, to sort the list: a tuple of the time-decayed
+/// score of the runs from the current folder (when history_by_folder is
+/// enabled), the time-decayed score of all the runs, and the index of the
+/// last run, so that between two equal scores the latest wins.
 fn get_ranked_items(
-    history_executed_actions: &[ExecutedAction],
-) -> HashMap<ExecutedAction, (usize, usize)> {
-    history_executed_actions
+    history_entries: &[ActionHistoryEntry],
+    cli_config: &config::CliConfig,
+) -> HashMap<ExecutedAction, (u64, u64, usize)> {
+    let folder = if cli_config.history_by_folder {
+        env::current_dir().ok()
+    } else {
+        None
+    };
+    rank_history_entries(history_entries, now_timestamp(), folder.as_deref())
+}
+
+/// frecency ranking: each past run of an action adds to its score,
+/// recent runs counting more than old ones. If we got a folder, actions
+/// run from that folder come first. The last run is the tie-breaker.
+fn rank_history_entries(
+    history_entries: &[ActionHistoryEntry],
+    now: u64,
+    folder: Option<&Path>,
+) -> HashMap<ExecutedAction, (u64, u64, usize)> {
+    history_entries
         .iter()
         .enumerate()
         .fold(HashMap::new(), |mut sofar, (i, cur)| {
-            let sofar_pair = sofar.entry(*cur).or_insert((0, i));
-            sofar_pair.0 += 1;
-            sofar_pair.1 = i;
+            let weight = frecency_weight(cur.timestamp, now);
+            let rank = sofar.entry(cur.action).or_insert((0, 0, i));
+            if folder.is_some() && cur.folder.as_deref() == folder {
+                rank.0 += weight;
+            }
+            rank.1 += weight;
+            rank.2 = i;
             sofar
         })
 }
 
+/// the weight halves every two weeks. The entries from older versions
+/// of ppcli have no timestamp, consider them old.
+fn frecency_weight(timestamp: Option<u64>, now: u64) -> u64 {
+    const HALF_LIFE_SECS: f64 = 14.0 * 24.0 * 3600.0;
+    const NO_TIMESTAMP_AGE_SECS: u64 = 90 * 24 * 3600;
+    let age = timestamp
+        .map(|t| now.saturating_sub(t))
+        .unwrap_or(NO_TIMESTAMP_AGE_SECS);
+    (1000.0 * 0.5f64.powf(age as f64 / HALF_LIFE_SECS)).round() as u64
+}
+
 fn run_command_folder(action: &actions::Action) -> Option<PathBuf> {
     Some(&action.item)
         .filter(|p| p.server_info.is_none()) // remote paths are not relevant!
@@ -348,12 +682,14 @@ fn check_db_version(conn: &mut SqliteConnection) -> Result<(), Box<dyn std::erro
     Ok(())
 }
 
-fn run_command(command_line: &str, cur_dir: &Path) {
+fn run_command(command_line: &str, cur_dir: &Path) -> Option<ExitStatus> {
     let cl_elts = shell_words::split(command_line).unwrap_or_else(|e| {
         println!("Couldn't parse the command: {}: {}", command_line, e);
         Vec::new()
     });
-    if !cl_elts.is_empty() {
+    if cl_elts.is_empty() {
+        None
+    } else {
         // the reason for the println is that some commands need
         // some time before they print out any output -- for instance
         // ssh on a far, slow server. With this println we give some
@@ -368,82 +704,94 @@ fn run_command(command_line: &str, cur_dir: &Path) {
             .args(cl_elts.iter().skip(1))
             .current_dir::<&Path>(actual_dir.borrow())
             .status()
-            .map(|_| ())
-            .unwrap_or_else(|e| {
+            .map_err(|e| {
                 println!("Error launching process: {}", e);
-            });
+            })
+            .ok()
     }
 }
 
-fn copy_command_to_clipboard(command_line: &str) {
-    // there are libraries for that in rust, earlier i was using
-    // clibpoard-ext, but:
-    // - there are issues with keeping the contents of the clipboard
-    //   after the app exits (need to fork, stay alive..)
-    // - must link to a series of X11 or wayland-related libraries,
-    //   on linux. But I want a static build so that i can distribute
-    //   a cross-distro binary.
-    // due to that, rather leverage wl-copy and xsel
-    // it seems xsel is a better choice than xclip:
-    // https://askubuntu.com/questions/705620/xclip-vs-xsel/898094#898094
-
-    // detect wayland or X11 https://unix.stackexchange.com/a/559950/36566
-    if std::env::var("WAYLAND_DISPLAY").is_ok() {
-        match Command::new("wl-copy")
-            .arg(command_line)
-            .spawn()
-            .and_then(|mut p| p.wait())
-        {
-            Result::Err(e) => eprintln!("Failed to invoke wl-copy: {}", e),
-            Result::Ok(s) if !s.success() => eprintln!("Got error status from wl-copy: {}", s),
-            _ => {}
+fn write_command_line_to_terminal(command_line: &str, paste_method: PasteMethod) {
+    let pasted = match paste_method {
+        PasteMethod::Auto => {
+            // TIOCSTI is disabled by default on recent kernels
+            // (dev.tty.legacy_tiocsti=0), in that case try the terminal
+            // multiplexer or emulator we're running in.
+            write_command_line_tiocsti(command_line)
+                || (env::var("TMUX").is_ok() && write_command_line_tmux(command_line))
+                || (env::var("KITTY_WINDOW_ID").is_ok() && write_command_line_kitty(command_line))
+                || (env::var("WEZTERM_PANE").is_ok() && write_command_line_wezterm(command_line))
         }
-    } else if std::env::var("DISPLAY").is_ok() {
-        // https://stackoverflow.com/a/49597789/516188
-        if let Result::Err(e) = Command::new("xsel")
-            .arg("--clipboard")
-            .stdin(Stdio::piped())
-            .spawn()
-            .and_then(|mut child| {
-                let child_stdin = child.stdin.as_mut().unwrap();
-                let write_res = child_stdin.write_all(command_line.as_bytes());
-                if write_res.is_err() {
-                    write_res
-                } else {
-                    let wait_res = child.wait();
-                    match wait_res {
-                        Result::Ok(s) if !s.success() => Result::Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("error status: {}", s),
-                        )),
-                        _ => wait_res.map(|_| ()),
-                    }
-                }
-            })
-        {
-            eprintln!("Error in xsel: {:?}", e);
+        PasteMethod::Tiocsti => write_command_line_tiocsti(command_line),
+        PasteMethod::Tmux => write_command_line_tmux(command_line),
+        PasteMethod::Kitty => write_command_line_kitty(command_line),
+        PasteMethod::Wezterm => write_command_line_wezterm(command_line),
+        PasteMethod::Print => false,
+    };
+    if !pasted {
+        // last resort: let the user copy the command
+        println!("{}", command_line);
+    }
+}
+
+/// the width of the terminal, through /dev/tty because in
+/// shell integration mode our stdout is captured
+fn terminal_width() -> Option<usize> {
+    let tty = std::fs::File::open("/dev/tty").ok()?;
+    unsafe {
+        let mut winsize: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut winsize) == 0 && winsize.ws_col > 0 {
+            Some(winsize.ws_col as usize)
+        } else {
+            None
         }
-    } else {
-        eprintln!("The system seems to be neither wayland nor X11, don't know how to copy to the clipboard");
     }
 }
 
-fn write_command_line_to_terminal(command_line: &str) {
+fn write_command_line_tiocsti(command_line: &str) -> bool {
     // https://unix.stackexchange.com/questions/213799/can-bash-write-to-its-own-input-stream/213821#213821
     unsafe {
         for byte in command_line.bytes() {
-            libc::ioctl(libc::STDIN_FILENO, libc::TIOCSTI, &byte);
+            if libc::ioctl(libc::STDIN_FILENO, libc::TIOCSTI, &byte) != 0 {
+                // if the first byte fails, nothing was written. Otherwise
+                // we'll print the command for the user, who can
+                // clear the partial input.
+                return false;
+            }
         }
     }
+    true
+}
+
+fn run_paste_command(program: &str, args: &[&str]) -> bool {
+    match Command::new(program).args(args).status() {
+        Ok(s) if s.success() => true,
+        Ok(s) => {
+            eprintln!("Got error status from {}: {}", program, s);
+            false
+        }
+        Err(e) => {
+            eprintln!("Failed to invoke {}: {}", program, e);
+            false
+        }
+    }
+}
 
-    // this code requires tmux. the ioctl is considered unsafe by some,
-    // the tmux way could become more portable in the future possible?
-    //
-    // std::process::Command::new("tmux")
-    //     .arg("send-key")
-    //     .arg(&myitem.command)
-    //     .status()
-    //     .unwrap();
+fn write_command_line_tmux(command_line: &str) -> bool {
+    // -l: send the keys literally, don't try to parse key names
+    run_paste_command("tmux", &["send-keys", "-l", "--", command_line])
+}
+
+fn write_command_line_kitty(command_line: &str) -> bool {
+    // requires allow_remote_control in the kitty configuration
+    run_paste_command("kitty", &["@", "send-text", "--", command_line])
+}
+
+fn write_command_line_wezterm(command_line: &str) -> bool {
+    run_paste_command(
+        "wezterm",
+        &["cli", "send-text", "--no-paste", "--", command_line],
+    )
 }
 
 #[test]
@@ -453,13 +801,83 @@ fn remove_ansi_escapes_prd() {
 
 #[test]
 fn get_ranked_items_should_work() {
+    let entry = |action, timestamp, folder: &str| ActionHistoryEntry {
+        action,
+        placeholder_values: vec![],
+        timestamp,
+        folder: Some(PathBuf::from(folder)),
+    };
     let action1 = ExecutedAction::new(LinkedItemId::Server(6), ActionType::SshShell);
     let action2 = ExecutedAction::new(LinkedItemId::ServerPoi(2), ActionType::FetchCfg);
     let action3 = ExecutedAction::new(LinkedItemId::Server(3), ActionType::SshShell);
+    // without timestamps, the actions are ranked by count
     assert_eq!(
-        vec![(action1, (3, 4)), (action3, (2, 5)), (action2, (1, 1))]
-            .into_iter()
-            .collect::<HashMap<_, _>>(),
-        get_ranked_items(&[action1, action2, action3, action1, action1, action3])
-    )
+        vec![
+            (action1, (0, 36, 4)),
+            (action3, (0, 24, 5)),
+            (action2, (0, 12, 1))
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>(),
+        rank_history_entries(
+            &[action1, action2, action3, action1, action1, action3]
+                .iter()
+                .map(|a| entry(*a, None, "/a"))
+                .collect::<Vec<_>>(),
+            0,
+            None
+        )
+    );
+    // a recent run outranks several old runs
+    let now = 100 * 24 * 3600;
+    let entries = vec![
+        entry(action1, Some(0), "/a"),
+        entry(action1, Some(0), "/a"),
+        entry(action2, Some(now - 3600), "/b"),
+    ];
+    let ranked = rank_history_entries(&entries, now, None);
+    assert!(ranked[&action2] > ranked[&action1]);
+    // unless we rank by folder
+    let ranked = rank_history_entries(&entries, now, Some(Path::new("/a")));
+    assert!(ranked[&action1] > ranked[&action2]);
+}
+
+#[test]
+fn shell_integration_output_run() {
+    assert_eq!(
+        "R\x00tail -f app.log\x00/opt/app\x00",
+        shell_integration_output(ShellIntegrationOp::Run, "tail -f app.log", "/opt/app", "")
+    );
+}
+
+#[test]
+fn shell_integration_output_copy_with_upgrade() {
+    let output = shell_integration_output(
+        ShellIntegrationOp::Copy,
+        "ssh user@srv",
+        "",
+        "https://example.com/ppcli.tgz",
+    );
+    assert_eq!(
+        vec!["C", "ssh user@srv", "", "https://example.com/ppcli.tgz"],
+        output.split('\x00').collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn secrets_are_only_copied() {
+    assert_eq!(
+        Some(Key::AltEnter),
+        accept_key_for_secrets(&[false, false], Key::AltEnter)
+    );
+    assert_eq!(
+        Some(Key::Ctrl('y')),
+        accept_key_for_secrets(&[true], Key::Enter)
+    );
+    assert_eq!(
+        Some(Key::Ctrl('y')),
+        accept_key_for_secrets(&[true, true], Key::AltEnter)
+    );
+    assert_eq!(None, accept_key_for_secrets(&[true, false], Key::Ctrl('y')));
+    assert_eq!(None, accept_key_for_secrets(&[false, true], Key::Enter));
 }
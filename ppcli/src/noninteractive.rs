@@ -0,0 +1,476 @@
+// non-interactive mode: list, print or run actions without going
+// through skim, for use in scripts.
+use crate::actions::{self, Action, AllowedAction};
+use crate::authkeys;
+use crate::clipboard;
+use crate::config::CliConfig;
+use crate::database::{
+    self, ActionHistoryEntry, DisplayMode, ExecutedAction, ItemsFilter, LinkedItemId, RowLayout,
+};
+use crate::fanout;
+use crate::forward;
+use crate::notes;
+use crate::placeholders;
+use crate::prod_guard;
+use crate::push;
+use crate::rdp;
+use diesel::sqlite::SqliteConnection;
+use projectpadsql::models::EnvironmentType;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use structopt::clap::{arg_enum, AppSettings};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub enum SubCommand {
+    /// List the actions, without the interactive UI
+    List {
+        /// Output format: tsv or json
+        #[structopt(long, default_value = "tsv")]
+        format: OutputFormat,
+    },
+    /// Print the command of the best action matching the query
+    Show {
+        /// Words to search for, as if they were typed in the interactive UI
+        #[structopt(required = true)]
+        query: Vec<String>,
+    },
+    /// Run the best action matching the query
+    Run {
+        /// Words to search for, as if they were typed in the interactive UI
+        #[structopt(required = true)]
+        query: Vec<String>,
+    },
+    /// Copy the best action matching the query to the clipboard, for instance a password
+    Copy {
+        /// Words to search for, as if they were typed in the interactive UI
+        #[structopt(required = true)]
+        query: Vec<String>,
+    },
+    /// Run a command in parallel on all the servers of a group
+    FanOut {
+        /// Run the command of that server point of interest
+        #[structopt(long, required_unless = "server")]
+        poi: Option<i32>,
+        /// Run the command given after -- on the servers of the group of that server.
+        /// Without a command, ask for it
+        #[structopt(long, conflicts_with = "poi")]
+        server: Option<i32>,
+        /// Run on all the servers of the environment, not only of the group
+        #[structopt(long)]
+        whole_env: bool,
+        /// Allow running on production servers, like the global --allow-prod
+        #[structopt(long = "allow-prod")]
+        fan_out_allow_prod: bool,
+        #[structopt(last = true)]
+        command: Vec<String>,
+    },
+    /// Forward a free local port to a database on a server, until ctrl-c
+    Forward {
+        /// The server database to forward to
+        #[structopt(long, required_unless = "server")]
+        database: Option<i32>,
+        /// The database server to forward to
+        #[structopt(long, conflicts_with = "database")]
+        server: Option<i32>,
+    },
+    /// Upload an edited copy of a configuration file to its server
+    Push {
+        /// The server point of interest of the configuration file
+        #[structopt(long)]
+        poi: i32,
+        /// The folder containing the edited copy, with the same file name
+        #[structopt(long)]
+        folder: PathBuf,
+    },
+    /// Open a remote desktop session on a RDP server
+    Rdp {
+        #[structopt(long)]
+        server: i32,
+    },
+    /// Show a project or server note in the pager
+    Note {
+        #[structopt(long, required_unless = "server-note")]
+        project_note: Option<i32>,
+        #[structopt(long, conflicts_with = "project-note")]
+        server_note: Option<i32>,
+    },
+    /// Run commands in parallel, prefixing their output lines with their names.
+    /// Used by ppcli itself, for several actions selected together
+    #[structopt(setting = AppSettings::Hidden)]
+    Parallel {
+        /// The name of each command, in the same order as the commands
+        #[structopt(long = "name")]
+        names: Vec<String>,
+        #[structopt(last = true, required = true)]
+        commands: Vec<String>,
+    },
+}
+
+arg_enum! {
+    #[derive(PartialEq, Eq)]
+    pub enum OutputFormat {
+        Tsv,
+        Json,
+    }
+}
+
+/// the options are global: they can be given before or after the subcommand
+#[derive(StructOpt)]
+pub struct ItemFilter {
+    /// Only consider items of that project (case-insensitive)
+    #[structopt(long, global = true)]
+    project: Option<String>,
+    /// Ignore the folder_project entries of ppcli.conf, show all the projects
+    #[structopt(long, global = true)]
+    all_projects: bool,
+    /// Only consider items of that environment: dev, stg, uat or prd
+    #[structopt(long, global = true, parse(try_from_str = parse_env))]
+    env: Option<EnvironmentType>,
+    /// Only consider items of that type, as displayed in the list: LOG, CFG, CMD, SRA...
+    #[structopt(long = "type", global = true)]
+    item_type: Option<String>,
+}
+
+pub fn parse_env(env: &str) -> Result<EnvironmentType, String> {
+    match env.to_lowercase().as_str() {
+        "dev" | "development" => Ok(EnvironmentType::EnvDevelopment),
+        "stg" | "stage" => Ok(EnvironmentType::EnvStage),
+        "uat" => Ok(EnvironmentType::EnvUat),
+        "prd" | "prod" => Ok(EnvironmentType::EnvProd),
+        _ => Err(format!(
+            "unknown environment {}, expected dev, stg, uat or prd",
+            env
+        )),
+    }
+}
+
+impl ItemFilter {
+    /// without --project, the project of the current folder in ppcli.conf, if any
+    pub fn items_filter(&self, cli_config: &CliConfig) -> ItemsFilter {
+        let project = match &self.project {
+            Some(project) => Some(project.clone()),
+            None if self.all_projects => None,
+            None => std::env::current_dir()
+                .ok()
+                .and_then(|d| cli_config.folder_project(&d).map(|p| p.to_string())),
+        };
+        ItemsFilter {
+            project,
+            env: self.env,
+            item_type: self.item_type.clone(),
+        }
+    }
+}
+
+/// mirrors the skim matching: all the words must be found
+/// (case-insensitive) in the item text.
+fn matches_query(layout: &RowLayout, action: &Action, query: &[String]) -> bool {
+    let text = (database::render_row(layout, action)
+        + "\n"
+        + action.item.poi_desc.as_deref().unwrap_or(""))
+    .to_lowercase();
+    query
+        .iter()
+        .flat_map(|q| q.split_whitespace())
+        .all(|word| text.contains(&word.to_lowercase()))
+}
+
+/// returns the exit code for the process
+pub fn run<T>(
+    conn: &mut SqliteConnection,
+    subcommand: SubCommand,
+    filter: &ItemsFilter,
+    ranked_items: &HashMap<ExecutedAction, T>,
+    history_entries: &[ActionHistoryEntry],
+    cli_config: &CliConfig,
+    allow_prod: bool,
+) -> i32
+where
+    T: Ord,
+{
+    let mut load_actions = || database::load_actions(conn, filter, ranked_items, &cli_config.sort);
+    let layout = RowLayout::new(cli_config, DisplayMode::Plain, None);
+    match subcommand {
+        SubCommand::List { format } => {
+            let actions = load_actions();
+            let actions: Vec<_> = actions.iter().collect();
+            match format {
+                OutputFormat::Tsv => print_tsv(&actions),
+                OutputFormat::Json => print_json(&actions),
+            }
+            0
+        }
+        SubCommand::Show { query } => {
+            let actions = load_actions();
+            match find_best_match(&actions, &layout, &query) {
+                Some(action) if action.allowed_actions == [AllowedAction::CopyToClipboard] => {
+                    // never print passwords
+                    eprintln!(
+                        "The action '{}' for '{}' can only be copied, use ppcli copy",
+                        action.desc,
+                        action.item.poi_desc.as_deref().unwrap_or("")
+                    );
+                    1
+                }
+                Some(action) => match fill_placeholders(action, history_entries, allow_prod) {
+                    Some((command, _)) => {
+                        authkeys::materialize_item_auth_key(&action.item);
+                        authkeys::schedule_auth_keys_removal(
+                            std::iter::once(&action.item),
+                            cli_config.auth_key_lifetime_secs,
+                        );
+                        println!("{}", command);
+                        0
+                    }
+                    None => 1,
+                },
+                None => 1,
+            }
+        }
+        SubCommand::Copy { query } => {
+            let actions = load_actions();
+            match find_best_match(&actions, &layout, &query) {
+                Some(action) => match fill_placeholders(action, history_entries, allow_prod) {
+                    Some((command, _)) => {
+                        authkeys::materialize_item_auth_key(&action.item);
+                        authkeys::schedule_auth_keys_removal(
+                            std::iter::once(&action.item),
+                            cli_config.auth_key_lifetime_secs,
+                        );
+                        let secret = action.allowed_actions == [AllowedAction::CopyToClipboard];
+                        clipboard::copy(&command, cli_config, secret);
+                        0
+                    }
+                    None => 1,
+                },
+                None => 1,
+            }
+        }
+        SubCommand::Run { query } => {
+            let actions = load_actions();
+            match find_best_match(&actions, &layout, &query) {
+                Some(action) if !action.allowed_actions.contains(&AllowedAction::Run) => {
+                    eprintln!(
+                        "The action '{}' for '{}' can't be run",
+                        action.desc,
+                        action.item.poi_desc.as_deref().unwrap_or("")
+                    );
+                    1
+                }
+                Some(action)
+                    if !prod_guard::can_run(action, cli_config, allow_prod, DisplayMode::Plain) =>
+                {
+                    1
+                }
+                Some(action) => match fill_placeholders(action, history_entries, allow_prod) {
+                    Some((command, folder)) => {
+                        authkeys::materialize_item_auth_key(&action.item);
+                        let folder = folder.unwrap_or_else(|| dirs::home_dir().unwrap());
+                        let status = crate::run_command(&command, &folder);
+                        authkeys::remove_item_auth_key(&action.item);
+                        status.and_then(|s| s.code()).unwrap_or(1)
+                    }
+                    None => 1,
+                },
+                None => 1,
+            }
+        }
+        SubCommand::FanOut {
+            poi,
+            server,
+            whole_env,
+            fan_out_allow_prod,
+            command,
+        } => fanout::run(
+            conn,
+            poi,
+            server,
+            whole_env,
+            &command,
+            cli_config,
+            allow_prod || fan_out_allow_prod,
+        ),
+        SubCommand::Forward { database, server } => forward::run(conn, database, server),
+        SubCommand::Push { poi, folder } => push::run(conn, poi, &folder, cli_config),
+        SubCommand::Rdp { server } => rdp::run(conn, server),
+        SubCommand::Note {
+            project_note: Some(id),
+            ..
+        } => notes::run(conn, LinkedItemId::ProjectNote(id)),
+        SubCommand::Note {
+            server_note: Some(id),
+            ..
+        } => notes::run(conn, LinkedItemId::ServerNote(id)),
+        SubCommand::Note { .. } => 1,
+        // run before unlocking the database, in main
+        SubCommand::Parallel { .. } => unreachable!(),
+    }
+}
+
+/// the values are prompted on the terminal, with the values from the
+/// last interactive run as defaults
+fn fill_placeholders(
+    action: &Action,
+    history_entries: &[ActionHistoryEntry],
+    allow_prod: bool,
+) -> Option<(String, Option<PathBuf>)> {
+    if action.allowed_actions == [AllowedAction::CopyToClipboard] {
+        // secrets, no placeholders there
+        return Some(((action.get_string)(&action.item).to_string(), None));
+    }
+    let res = placeholders::fill_action_placeholders(
+        &actions::forward_allow_prod(action, (action.get_string)(&action.item), allow_prod),
+        crate::run_command_folder(action),
+        &placeholders::previous_values(
+            history_entries,
+            ExecutedAction::new(action.item.linked_item, action.desc),
+        ),
+    );
+    if res.is_none() {
+        eprintln!("Cancelled.");
+    }
+    res.map(|(command, folder, _)| (command, folder))
+}
+
+fn find_best_match<'a>(
+    actions: &'a [Action],
+    layout: &RowLayout,
+    query: &[String],
+) -> Option<&'a Action> {
+    // the actions are sorted by relevance, the first match is the best one
+    let res = actions.iter().find(|a| matches_query(layout, a, query));
+    if res.is_none() {
+        eprintln!("No action matches the query {}", query.join(" "));
+    }
+    res
+}
+
+struct ActionFields {
+    project: String,
+    env: String,
+    item_type: String,
+    server: String,
+    server_ip: String,
+    desc: String,
+    path: String,
+    action: String,
+    command: String,
+}
+
+fn action_fields(action: &Action) -> ActionFields {
+    let item = &action.item;
+    ActionFields {
+        project: item.project_name.clone(),
+        env: item
+            .env
+            .as_ref()
+            .map(database::display_env)
+            .unwrap_or("")
+            .to_string(),
+        item_type: database::render_type(&item.item_type).to_string(),
+        server: item
+            .server_info
+            .as_ref()
+            .map(|s| s.server_desc.clone())
+            .unwrap_or_default(),
+        server_ip: item
+            .server_info
+            .as_ref()
+            .map(|s| s.server_ip.clone())
+            .unwrap_or_default(),
+        desc: item.poi_desc.clone().unwrap_or_default(),
+        path: item
+            .poi_info
+            .as_ref()
+            .map(|p| p.path.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        action: action.desc.to_string(),
+        // don't print out secrets when listing
+        command: if action.allowed_actions == [AllowedAction::CopyToClipboard] {
+            "".to_string()
+        } else {
+            placeholders::fill_default_folders(&(action.get_string)(item)).to_string()
+        },
+    }
+}
+
+fn print_tsv(actions: &[&Action]) {
+    for action in actions {
+        let f = action_fields(action);
+        println!(
+            "{}",
+            [
+                f.project,
+                f.env,
+                f.item_type,
+                f.server,
+                f.server_ip,
+                f.desc,
+                f.path,
+                f.action,
+                f.command
+            ]
+            .iter()
+            .map(|c| c.replace(&['\t', '\n'][..], " "))
+            .collect::<Vec<_>>()
+            .join("\t")
+        );
+    }
+}
+
+// i don't want to pull serde_json in ppcli only for that
+fn json_string(input: &str) -> String {
+    let mut result = String::with_capacity(input.len() + 2);
+    result.push('"');
+    for c in input.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn print_json(actions: &[&Action]) {
+    let rows: Vec<_> = actions
+        .iter()
+        .map(|action| {
+            let f = action_fields(action);
+            format!(
+                "{{\"project\":{},\"env\":{},\"type\":{},\"server\":{},\"server_ip\":{},\"desc\":{},\"path\":{},\"action\":{},\"command\":{}}}",
+                json_string(&f.project),
+                json_string(&f.env),
+                json_string(&f.item_type),
+                json_string(&f.server),
+                json_string(&f.server_ip),
+                json_string(&f.desc),
+                json_string(&f.path),
+                json_string(&f.action),
+                json_string(&f.command),
+            )
+        })
+        .collect();
+    println!("[{}]", rows.join(",\n"));
+}
+
+#[test]
+fn json_string_escapes() {
+    assert_eq!(
+        "\"cat \\\"a\\\\b\\\"\\n\\u0001\"",
+        json_string("cat \"a\\b\"\n\u{1}")
+    );
+}
+
+#[test]
+fn parse_env_accepts_short_and_long_names() {
+    assert_eq!(Ok(EnvironmentType::EnvProd), parse_env("PRD"));
+    assert_eq!(Ok(EnvironmentType::EnvStage), parse_env("stage"));
+    assert!(parse_env("qa").is_err());
+}
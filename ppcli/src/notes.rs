@@ -0,0 +1,50 @@
+// viewing a whole project or server note in the terminal. The note is
+// rendered to text, with the passwords hidden, and shown in the pager.
+use crate::database::{self, LinkedItemId};
+use diesel::sqlite::SqliteConnection;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// returns the exit code for the process
+pub fn run(conn: &mut SqliteConnection, note: LinkedItemId) -> i32 {
+    let (title, contents) = match database::load_note(conn, note) {
+        Some(n) => n,
+        None => {
+            eprintln!("No such note: {:?}", note);
+            return 1;
+        }
+    };
+    let text = format!(
+        "# {}\n\n{}",
+        title,
+        projectpadsql::notes::note_markdown_to_plain_text(&contents)
+    );
+    match show_in_pager(&text) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn show_in_pager(text: &str) -> Result<(), Box<dyn Error>> {
+    if unsafe { libc::isatty(libc::STDOUT_FILENO) } == 0 {
+        // piped to another program
+        print!("{}", text);
+        return Ok(());
+    }
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let cl_elts = shell_words::split(&pager)?;
+    let program = cl_elts.first().ok_or("PAGER is empty")?;
+    let mut child = Command::new(program)
+        .args(&cl_elts[1..])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Error running the pager {}: {}", pager, e))?;
+    // the user may quit the pager before it read everything
+    let _ = child.stdin.take().unwrap().write_all(text.as_bytes());
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,269 @@
+// placeholders in the text and path of POIs, which are filled when the
+// action is selected, so that a single POI can cover commands which differ
+// only by a date or a tenant id:
+// - {{date}}: the current date, 2021-03-25
+// - {{ask:ticket id}}: prompt the user for the value
+// - {{choice:a|b}}: let the user pick one of the values
+// - {{folder:destination}}: prompt for a folder, the download folder by
+//   default. It's inserted quoted for the shell. Without a terminal, the
+//   default is used without asking.
+use crate::database::{ActionHistoryEntry, ExecutedAction};
+use crate::prompt::{ask_on_tty, has_tty};
+use regex::Regex;
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// the placeholders (without the braces) and the values given for them
+pub type PlaceholderValues = Vec<(String, String)>;
+
+fn placeholder_regex() -> Regex {
+    Regex::new(r"\{\{(.*?)\}\}").unwrap()
+}
+
+/// replace the placeholders in the input. Values already in `values` are
+/// reused (the same placeholder can appear in the command and in the folder),
+/// and new values are added to it. `previous_values` are offered as defaults.
+/// Returns None if the user didn't give a value.
+pub fn fill_placeholders(
+    input: &str,
+    values: &mut PlaceholderValues,
+    previous_values: &[(String, String)],
+) -> Option<String> {
+    let mut result = String::new();
+    let mut last_end = 0;
+    for captures in placeholder_regex().captures_iter(input) {
+        let whole = captures.get(0).unwrap();
+        let placeholder = captures[1].trim();
+        result.push_str(&input[last_end..whole.start()]);
+        last_end = whole.end();
+        if let Some((_, v)) = values.iter().find(|(p, _)| p == placeholder) {
+            result.push_str(&value_to_insert(placeholder, v));
+            continue;
+        }
+        let default = previous_values
+            .iter()
+            .rev()
+            .find(|(p, _)| p == placeholder)
+            .map(|(_, v)| v.as_str());
+        match get_placeholder_value(placeholder, default) {
+            PlaceholderValue::Unknown => result.push_str(whole.as_str()),
+            PlaceholderValue::Value(v) => {
+                result.push_str(&value_to_insert(placeholder, &v));
+                values.push((placeholder.to_string(), v));
+            }
+            PlaceholderValue::Cancelled => return None,
+        }
+    }
+    result.push_str(&input[last_end..]);
+    Some(result)
+}
+
+/// the folder placeholders replaced by the default folder, for when
+/// there is nobody to ask, for instance when listing the commands
+pub fn fill_default_folders(input: &str) -> Cow<'_, str> {
+    placeholder_regex().replace_all(input, |captures: &regex::Captures| {
+        match (captures[1].trim().starts_with("folder:"), default_folder()) {
+            (true, Some(f)) => shell_words::quote(&f.to_string_lossy()).to_string(),
+            _ => captures[0].to_string(),
+        }
+    })
+}
+
+fn default_folder() -> Option<PathBuf> {
+    dirs::download_dir()
+        .filter(|d| d.is_dir())
+        .or_else(dirs::home_dir)
+}
+
+fn value_to_insert<'a>(placeholder: &str, value: &'a str) -> Cow<'a, str> {
+    if placeholder.starts_with("folder:") {
+        shell_words::quote(value)
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+enum PlaceholderValue {
+    /// not a placeholder we know about, leave it as-is
+    Unknown,
+    Value(String),
+    Cancelled,
+}
+
+/// the values the user gave previously for the placeholders of that action
+pub fn previous_values(
+    history: &[ActionHistoryEntry],
+    action: ExecutedAction,
+) -> Vec<(String, String)> {
+    history
+        .iter()
+        .filter(|e| e.action == action)
+        .flat_map(|e| e.placeholder_values.iter().cloned())
+        .collect()
+}
+
+/// fill the placeholders of a command and of the folder to run it in
+pub fn fill_action_placeholders(
+    command: &str,
+    folder: Option<PathBuf>,
+    previous_values: &[(String, String)],
+) -> Option<(String, Option<PathBuf>, PlaceholderValues)> {
+    let mut values = vec![];
+    let command = fill_placeholders(command, &mut values, previous_values)?;
+    let folder = match folder {
+        Some(f) => Some(PathBuf::from(fill_placeholders(
+            &f.to_string_lossy(),
+            &mut values,
+            previous_values,
+        )?)),
+        None => None,
+    };
+    Some((command, folder, values))
+}
+
+fn get_placeholder_value(placeholder: &str, default: Option<&str>) -> PlaceholderValue {
+    let default_display = default.map(|d| format!(" [{}]", d)).unwrap_or_default();
+    let answer_or_default = |answer: Option<String>| match (answer, default) {
+        (Some(a), _) if !a.is_empty() => Some(a),
+        (Some(_), Some(d)) => Some(d.to_string()),
+        _ => None,
+    };
+    if placeholder == "date" {
+        PlaceholderValue::Value(today())
+    } else if let Some(label) = placeholder.strip_prefix("ask:") {
+        match answer_or_default(ask_on_tty(&format!("{}{}: ", label, default_display))) {
+            Some(v) => PlaceholderValue::Value(v),
+            None => PlaceholderValue::Cancelled,
+        }
+    } else if let Some(choices_str) = placeholder.strip_prefix("choice:") {
+        let choices: Vec<_> = choices_str.split('|').collect();
+        let prompt = choices
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{}) {}", i + 1, c))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let answer = answer_or_default(ask_on_tty(&format!("{}{}: ", prompt, default_display)));
+        // the user can type the number of the choice, or the choice itself
+        match answer.and_then(|a| {
+            a.parse::<usize>()
+                .ok()
+                .and_then(|i| i.checked_sub(1))
+                .and_then(|i| choices.get(i))
+                .or_else(|| choices.iter().find(|c| **c == a))
+                .map(|c| c.to_string())
+        }) {
+            Some(v) => PlaceholderValue::Value(v),
+            None => PlaceholderValue::Cancelled,
+        }
+    } else if let Some(label) = placeholder.strip_prefix("folder:") {
+        ask_folder(label, default, has_tty())
+    } else {
+        PlaceholderValue::Unknown
+    }
+}
+
+fn ask_folder(label: &str, default: Option<&str>, interactive: bool) -> PlaceholderValue {
+    let default = default.map(PathBuf::from).or_else(default_folder);
+    if !interactive {
+        // cron, CI, ppcli show: don't fail, take the default
+        return folder_value(default);
+    }
+    let default_display = default
+        .as_ref()
+        .map(|d| format!(" [{}]", d.display()))
+        .unwrap_or_default();
+    folder_value(
+        match ask_on_tty(&format!("{}{}: ", label, default_display)) {
+            Some(a) if !a.is_empty() => Some(expand_tilde(&a)),
+            Some(_) => default,
+            None => None,
+        },
+    )
+}
+
+fn folder_value(folder: Option<PathBuf>) -> PlaceholderValue {
+    match folder {
+        Some(f) if f.is_dir() => PlaceholderValue::Value(f.to_string_lossy().to_string()),
+        Some(f) => {
+            eprintln!("{} is not a folder", f.display());
+            PlaceholderValue::Cancelled
+        }
+        None => PlaceholderValue::Cancelled,
+    }
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(format!("{}{}", home.display(), rest))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+fn today() -> String {
+    // i don't want to pull in chrono only for that
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        format!(
+            "{:04}-{:02}-{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday
+        )
+    }
+}
+
+#[test]
+fn fill_placeholders_reuses_known_values() {
+    let mut values = vec![("ask:ticket id".to_string(), "T-12".to_string())];
+    assert_eq!(
+        Some("deploy.sh T-12 /tmp/T-12 {{other}}".to_string()),
+        fill_placeholders(
+            "deploy.sh {{ask:ticket id}} /tmp/{{ ask:ticket id }} {{other}}",
+            &mut values,
+            &[]
+        )
+    );
+    assert_eq!(1, values.len());
+}
+
+#[test]
+fn fill_placeholders_quotes_folders() {
+    let mut values = vec![(
+        "folder:destination".to_string(),
+        "/home/user/my files".to_string(),
+    )];
+    assert_eq!(
+        Some("scp srv:/etc/app.conf '/home/user/my files'".to_string()),
+        fill_placeholders(
+            "scp srv:/etc/app.conf {{folder:destination}}",
+            &mut values,
+            &[]
+        )
+    );
+}
+
+#[test]
+fn fill_default_folders_without_prompting() {
+    let folder = default_folder().unwrap();
+    assert_eq!(
+        format!(
+            "scp srv:/etc/app.conf {} {{{{ask:other}}}}",
+            shell_words::quote(&folder.to_string_lossy())
+        ),
+        fill_default_folders("scp srv:/etc/app.conf {{folder:destination}} {{ask:other}}")
+    );
+}
+
+#[test]
+fn folder_defaults_without_terminal() {
+    let tmp = std::env::temp_dir().to_string_lossy().to_string();
+    assert!(matches!(
+        ask_folder("destination", Some(&tmp), false),
+        PlaceholderValue::Value(v) if v == tmp
+    ));
+}
@@ -0,0 +1,122 @@
+// protect against running commands on production servers by mistake,
+// for instance after selecting the wrong row in the list.
+use crate::actions::Action;
+use crate::config::{CliConfig, ProdConfirmation};
+use crate::database::{ActionType, DisplayMode, ItemOfInterest, ItemType};
+use crate::prompt::ask_on_tty;
+use projectpadsql::models::{EnvironmentType, InterestType};
+
+fn is_prod_sensitive(action: &Action) -> bool {
+    action.item.env == Some(EnvironmentType::EnvProd)
+        && [ActionType::RunCmd, ActionType::EditCfg].contains(&action.desc)
+}
+
+/// returns true if the action can be run. May prompt the user.
+pub fn can_run(
+    action: &Action,
+    config: &CliConfig,
+    allow_prod: bool,
+    display_mode: DisplayMode,
+) -> bool {
+    if action.item.env != Some(EnvironmentType::EnvProd) {
+        return true;
+    }
+    if config.prod_commands_require_allow_prod
+        && !allow_prod
+        && [
+            ItemType::InterestItemType(InterestType::PoiCommandToRun),
+            ItemType::Snippet,
+        ]
+        .contains(&action.item.item_type)
+    {
+        eprintln!("Refusing to run a command on a production server without --allow-prod");
+        return false;
+    }
+    if !is_prod_sensitive(action) {
+        return true;
+    }
+    let server_name = action
+        .item
+        .server_info
+        .as_ref()
+        .map(|s| s.server_desc.as_str())
+        .or(action.item.poi_desc.as_deref())
+        .unwrap_or("");
+    confirm(&action.desc.to_string(), server_name, config, display_mode)
+}
+
+/// returns true if the command can be run on all these servers.
+/// May prompt the user.
+pub fn can_fan_out(
+    servers: &[ItemOfInterest],
+    group_desc: &str,
+    config: &CliConfig,
+    allow_prod: bool,
+    display_mode: DisplayMode,
+) -> bool {
+    if !servers
+        .iter()
+        .any(|s| s.env == Some(EnvironmentType::EnvProd))
+    {
+        return true;
+    }
+    if config.prod_commands_require_allow_prod && !allow_prod {
+        eprintln!("Refusing to run a command on production servers without --allow-prod");
+        return false;
+    }
+    confirm(
+        &ActionType::RunOnGroup.to_string(),
+        group_desc,
+        config,
+        display_mode,
+    )
+}
+
+/// returns true if the file can be overwritten on that server.
+/// May prompt the user.
+pub fn can_push(item: &ItemOfInterest, config: &CliConfig, display_mode: DisplayMode) -> bool {
+    if item.env != Some(EnvironmentType::EnvProd) {
+        return true;
+    }
+    let server_name = item
+        .server_info
+        .as_ref()
+        .map(|s| s.server_desc.as_str())
+        .unwrap_or("");
+    confirm(
+        &ActionType::PushCfg.to_string(),
+        server_name,
+        config,
+        display_mode,
+    )
+}
+
+fn confirm(
+    action_desc: &str,
+    server_name: &str,
+    config: &CliConfig,
+    display_mode: DisplayMode,
+) -> bool {
+    let (start_color, end_color) = if display_mode == DisplayMode::Color {
+        ("\x1b[31m\x1b[1m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+    match config.prod_confirmation {
+        ProdConfirmation::None => true,
+        ProdConfirmation::Confirm => {
+            let answer = ask_on_tty(&format!(
+                "{}'{}' on the PRODUCTION server {}, are you sure? y/n{} ",
+                start_color, action_desc, server_name, end_color
+            ));
+            matches!(answer.as_deref(), Some("y") | Some("Y"))
+        }
+        ProdConfirmation::TypeServerName => {
+            let answer = ask_on_tty(&format!(
+                "{}'{}' on the PRODUCTION server, type the server name ({}) to confirm:{} ",
+                start_color, action_desc, server_name, end_color
+            ));
+            answer.as_deref() == Some(server_name)
+        }
+    }
+}
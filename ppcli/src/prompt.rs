@@ -0,0 +1,63 @@
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+
+/// false when running from cron or CI for instance: then we can't ask
+pub fn has_tty() -> bool {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .is_ok()
+}
+
+/// read the answer from the terminal rather than stdin: in shell integration
+/// mode our stdout is captured, and in non-interactive mode stdin may be
+/// redirected. If there is no terminal, there is no answer.
+pub fn ask_on_tty(prompt: &str) -> Option<String> {
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    tty.write_all(prompt.as_bytes()).ok()?;
+    tty.flush().ok()?;
+    let mut answer = String::new();
+    BufReader::new(tty).read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+/// same as ask_on_tty, but doesn't echo the answer
+pub fn ask_password_on_tty(prompt: &str) -> Option<String> {
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+    let orig_termios = unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return None;
+        }
+        let orig = termios;
+        termios.c_lflag &= !libc::ECHO;
+        libc::tcsetattr(fd, libc::TCSANOW, &termios);
+        orig
+    };
+    let answer = tty
+        .write_all(prompt.as_bytes())
+        .and_then(|_| tty.flush())
+        .and_then(|_| {
+            let mut answer = String::new();
+            BufReader::new(&tty).read_line(&mut answer).map(|_| answer)
+        });
+    unsafe {
+        libc::tcsetattr(fd, libc::TCSANOW, &orig_termios);
+    }
+    // the newline typed by the user was not echoed
+    let _ = tty.write_all(b"\n");
+    answer
+        .ok()
+        .map(|a| a.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
@@ -0,0 +1,134 @@
+// upload an edited copy of a configuration file back to its server.
+// We show the differences with the file on the server first, and after
+// confirmation, we back up the remote file next to it and overwrite it.
+use crate::actions;
+use crate::authkeys;
+use crate::config::CliConfig;
+use crate::database::{self, DisplayMode, ItemOfInterest};
+use crate::prod_guard;
+use crate::prompt::ask_on_tty;
+use diesel::sqlite::SqliteConnection;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// returns the exit code for the process
+pub fn run(conn: &mut SqliteConnection, poi_id: i32, folder: &Path, cli_config: &CliConfig) -> i32 {
+    let item = match database::load_server_poi(conn, poi_id) {
+        Some(item) if actions::is_ssh_access(&item) => item,
+        _ => {
+            eprintln!("No ssh server point of interest with id {}", poi_id);
+            return 1;
+        }
+    };
+    authkeys::materialize_item_auth_key(&item);
+    let result = push(&item, folder, cli_config);
+    authkeys::remove_item_auth_key(&item);
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn push(
+    item: &ItemOfInterest,
+    folder: &Path,
+    cli_config: &CliConfig,
+) -> Result<(), Box<dyn Error>> {
+    let remote_path = item.poi_info.as_ref().unwrap().path.to_string_lossy();
+    let file_name = Path::new(remote_path.as_ref())
+        .file_name()
+        .ok_or_else(|| format!("No file name in {}", remote_path))?;
+    let local_path = folder.join(file_name);
+    if !local_path.is_file() {
+        return Err(format!("{} doesn't exist", local_path.display()).into());
+    }
+    let server_desc = &item.server_info.as_ref().unwrap().server_desc;
+
+    let remote_contents = Command::new("sh")
+        .arg("-c")
+        .arg(ssh_command(
+            item,
+            &format!("cat {}", shell_words::quote(&remote_path)),
+        )?)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !remote_contents.status.success() {
+        return Err(format!("Error reading {} on {}", remote_path, server_desc).into());
+    }
+    if !show_diff(&remote_contents.stdout, &remote_path, &local_path)? {
+        println!("No differences, nothing to push.");
+        return Ok(());
+    }
+
+    let answer = ask_on_tty(&format!(
+        "Overwrite {} on {}? y/n ",
+        remote_path, server_desc
+    ));
+    if !matches!(answer.as_deref(), Some("y") | Some("Y"))
+        || !prod_guard::can_push(item, cli_config, DisplayMode::Plain)
+    {
+        return Err("Cancelled.".into());
+    }
+
+    // cat > keeps the owner and permissions of the remote file
+    let quoted_path = shell_words::quote(&remote_path);
+    let upload = format!(
+        "cp -p {0} {0}.\"$(date +%Y%m%d-%H%M%S)\".bak && cat > {0}",
+        quoted_path
+    );
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(ssh_command(item, &upload)?)
+        .stdin(File::open(&local_path)?)
+        .status()?;
+    if !status.success() {
+        return Err(format!(
+            "Error uploading {} to {}",
+            local_path.display(),
+            server_desc
+        )
+        .into());
+    }
+    println!(
+        "Pushed {}, the previous version was backed up next to it.",
+        remote_path
+    );
+    Ok(())
+}
+
+fn ssh_command(item: &ItemOfInterest, command: &str) -> Result<String, Box<dyn Error>> {
+    actions::get_ssh_command(item, command)
+        .ok_or_else(|| "Cannot build the ssh command for the server".into())
+}
+
+/// returns true if there are differences
+fn show_diff(
+    remote_contents: &[u8],
+    remote_path: &str,
+    local_path: &Path,
+) -> Result<bool, Box<dyn Error>> {
+    let mut diff = Command::new("diff")
+        .arg("-u")
+        .arg("-L")
+        .arg(format!("server:{}", remote_path))
+        .arg("-L")
+        .arg(local_path)
+        .arg("-")
+        .arg(local_path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Error running diff: {}", e))?;
+    // diff may exit before reading all its input
+    let _ = diff.stdin.take().unwrap().write_all(remote_contents);
+    match diff.wait()?.code() {
+        Some(0) => Ok(false),
+        Some(1) => Ok(true),
+        _ => Err("Error comparing the files".into()),
+    }
+}
@@ -0,0 +1,147 @@
+// remote desktop sessions on windows servers. The password must not be on
+// the command-line, where other users can see it (ps) and where it ends up
+// in the shell history, so we give it to xfreerdp on its stdin. remmina
+// has no such option, with remmina the user is prompted for the password,
+// which can be copied with the "copy pass" action.
+use crate::actions;
+use crate::database::{self, ServerInfo};
+use diesel::sqlite::SqliteConnection;
+use projectpadsql::models::ServerAccessType;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const FREERDP_PROGRAMS: [&str; 2] = ["xfreerdp", "xfreerdp3"];
+
+/// returns the exit code for the process
+pub fn run(conn: &mut SqliteConnection, server_id: i32) -> i32 {
+    let item = match database::load_server(conn, server_id) {
+        Some(item)
+            if item.server_info.as_ref().map(|s| s.server_access_type)
+                == Some(ServerAccessType::SrvAccessRdp) =>
+        {
+            item
+        }
+        _ => {
+            eprintln!("No RDP server with id {}", server_id);
+            return 1;
+        }
+    };
+    let server_info = item.server_info.as_ref().unwrap();
+    let result = if let Some(program) = FREERDP_PROGRAMS.iter().find(|p| actions::is_in_path(p)) {
+        run_freerdp(program, server_info)
+    } else if actions::is_in_path("remmina") {
+        run_remmina(server_info)
+    } else {
+        Err("Neither xfreerdp nor remmina are installed".to_string())
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// windows users may be given as DOMAIN\user
+fn split_domain(username: &str) -> (Option<&str>, &str) {
+    match username.split_once('\\') {
+        Some((domain, user)) => (Some(domain), user),
+        None => (None, username),
+    }
+}
+
+fn freerdp_args(server_info: &ServerInfo) -> Vec<String> {
+    let mut args = vec![
+        format!("/v:{}", server_info.server_ip),
+        "/dynamic-resolution".to_string(),
+        "+clipboard".to_string(),
+    ];
+    let (domain, user) = split_domain(&server_info.server_username);
+    if !user.is_empty() {
+        args.push(format!("/u:{}", user));
+    }
+    if let Some(domain) = domain {
+        args.push(format!("/d:{}", domain));
+    }
+    if !server_info.server_password.is_empty() {
+        // prompt for the missing credentials on stdin before connecting
+        args.push("/from-stdin:force".to_string());
+    }
+    args
+}
+
+/// what xfreerdp reads with /from-stdin: it prompts for the user and
+/// the domain if we didn't give them, then for the password
+fn freerdp_stdin(server_info: &ServerInfo) -> String {
+    let (domain, user) = split_domain(&server_info.server_username);
+    format!(
+        "{}{}{}\n",
+        if user.is_empty() { "\n" } else { "" },
+        if domain.is_none() { "\n" } else { "" },
+        server_info.server_password
+    )
+}
+
+fn run_freerdp(program: &str, server_info: &ServerInfo) -> Result<i32, String> {
+    let mut child = Command::new(program)
+        .args(freerdp_args(server_info))
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Error launching {}: {}", program, e))?;
+    let mut stdin = child.stdin.take().unwrap();
+    if !server_info.server_password.is_empty() {
+        stdin
+            .write_all(freerdp_stdin(server_info).as_bytes())
+            .map_err(|e| format!("Error giving the password to {}: {}", program, e))?;
+    }
+    drop(stdin);
+    child
+        .wait()
+        .map(|s| s.code().unwrap_or(1))
+        .map_err(|e| format!("Error running {}: {}", program, e))
+}
+
+fn run_remmina(server_info: &ServerInfo) -> Result<i32, String> {
+    let url = if server_info.server_username.is_empty() {
+        format!("rdp://{}", server_info.server_ip)
+    } else {
+        format!(
+            "rdp://{}@{}",
+            server_info.server_username, server_info.server_ip
+        )
+    };
+    Command::new("remmina")
+        .arg("-c")
+        .arg(url)
+        .status()
+        .map(|s| s.code().unwrap_or(1))
+        .map_err(|e| format!("Error running remmina: {}", e))
+}
+
+#[test]
+fn freerdp_args_split_the_domain() {
+    let server_info = ServerInfo {
+        server_id: 1,
+        server_desc: "win".to_string(),
+        server_username: "CORP\\admin".to_string(),
+        server_ip: "10.0.0.5:3390".to_string(),
+        server_access_type: ServerAccessType::SrvAccessRdp,
+        ssh_jump_hosts: vec![],
+        ssh_auth_key: None,
+        server_group: None,
+        server_password: "secret".to_string(),
+    };
+    assert_eq!(
+        vec![
+            "/v:10.0.0.5:3390",
+            "/dynamic-resolution",
+            "+clipboard",
+            "/u:admin",
+            "/d:CORP",
+            "/from-stdin:force"
+        ],
+        freerdp_args(&server_info)
+    );
+    assert_eq!("secret\n", freerdp_stdin(&server_info));
+}
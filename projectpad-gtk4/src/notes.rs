@@ -1,71 +1,12 @@
 use glib::translate::IntoGlib;
 use gtk::{gdk, prelude::*};
+use projectpadsql::notes::{get_events_with_passwords, EventExt};
 use pulldown_cmark::{Event, Options, Parser, Tag};
 use std::collections::HashMap;
 
 // TODO
 // <hr> doesn't exactly look great
 
-// cmark parses the passwords like so:
-// Text(Borrowed("[")) <-- opening bracket
-// Text(Borrowed("pass|XXX|")) <-- body
-// Text(Borrowed("]")) <-- closing bracket
-#[derive(PartialEq, Debug, Clone)]
-enum PassState<'a> {
-    None,
-    AfterOpeningBracket(EventExt<'a>),
-    AfterPass(Vec<EventExt<'a>>),
-    AfterBody(Vec<EventExt<'a>>, String),
-}
-
-#[derive(PartialEq, Debug, Clone)]
-enum EventExt<'a> {
-    StandardEvent(Event<'a>),
-    Password(String),
-}
-
-fn get_events_with_passwords(parser: Parser) -> Vec<EventExt> {
-    let mut pass_state = PassState::None;
-    parser.fold(vec![], |mut so_far, evt| match (&pass_state, evt) {
-        (_, Event::Text(v)) if v.as_ref() == "[" => {
-            pass_state = PassState::AfterOpeningBracket(EventExt::StandardEvent(Event::Text(v)));
-            so_far
-        }
-        (PassState::AfterOpeningBracket(e0), Event::Text(v)) if v.as_ref() == "pass" => {
-            pass_state = PassState::AfterPass(vec![
-                e0.clone(),
-                EventExt::StandardEvent(Event::Text(v.clone())),
-            ]);
-            so_far
-        }
-        (PassState::AfterPass(vec0), Event::Code(ref v)) => {
-            let pass = v.to_string();
-            let mut vec = vec0.clone();
-            vec.push(EventExt::StandardEvent(Event::Code(v.clone())));
-            pass_state = PassState::AfterBody(vec, pass);
-            so_far
-        }
-        (PassState::AfterBody(_, p), Event::Text(v)) if v.as_ref() == "]" => {
-            so_far.push(EventExt::Password(p.clone()));
-            pass_state = PassState::None;
-            so_far
-        }
-        (ps, evt) => {
-            // in case we were in the process of parsing a password and the parsing
-            // didn't conclude positively, flush back the events that I held back
-            match ps {
-                PassState::AfterOpeningBracket(e) => so_far.push(e.clone()),
-                PassState::AfterPass(es) => so_far.extend(es.clone()),
-                PassState::AfterBody(es, _) => so_far.extend(es.clone()),
-                _ => {}
-            }
-            pass_state = PassState::None;
-            so_far.push(EventExt::StandardEvent(evt.clone()));
-            so_far
-        }
-    })
-}
-
 const TAG_BOLD: &str = "bold";
 const TAG_ITALICS: &str = "italics";
 const TAG_STRIKETHROUGH: &str = "strikethrough";
@@ -223,26 +164,6 @@ pub struct NoteBufferInfo {
     pub header_iters: Vec<gtk::TextIter>,
 }
 
-pub fn note_markdown_to_quick_preview(input: &str) -> String {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext(input, options);
-    let events_with_passwords = get_events_with_passwords(parser);
-    let mut result = "".to_string();
-    for event in events_with_passwords {
-        match event {
-            EventExt::StandardEvent(Event::Text(t)) => result.push_str(&t),
-            EventExt::StandardEvent(Event::Code(t)) => result.push_str(&t),
-            EventExt::StandardEvent(Event::Start(Tag::Paragraph)) => result.push('\n'),
-            EventExt::StandardEvent(Event::End(Tag::Paragraph)) => result.push('\n'),
-            EventExt::StandardEvent(Event::End(Tag::Heading(_))) => result.push('\n'),
-            EventExt::Password(_) => result.push_str("[password]"),
-            _ => {}
-        }
-    }
-    result
-}
-
 // https://developer.gnome.org/pygtk/stable/pango-markup-language.html
 pub fn note_markdown_to_text_buffer(input: &str, table: &gtk::TextTagTable) -> NoteBufferInfo {
     let mut options = Options::empty();
@@ -484,81 +405,3 @@ fn get_blockquote_tag(blockquote_level: i32) -> Option<&'static str> {
         i32::MIN..=0 => None,
     }
 }
-
-#[test]
-fn add_password_events() {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext("hello *world [pass`se*c~~r*et`]*", options);
-    let evts = get_events_with_passwords(parser);
-    assert!(evts.iter().any(|e| match e {
-        EventExt::Password(p) if p == "se*c~~r*et" => true,
-        _ => false,
-    }));
-}
-
-#[test]
-fn add_password_events_backticks() {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext("hello *world [pass``sec`ret``]*", options);
-    let evts = get_events_with_passwords(parser);
-    assert!(evts.iter().any(|e| match e {
-        EventExt::Password(p) if p == "sec`ret" => true,
-        _ => false,
-    }));
-}
-
-#[test]
-fn add_password_events_double_backticks() {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext("hello *world [pass```sec``ret```]*", options);
-    let evts = get_events_with_passwords(parser);
-    assert!(evts.iter().any(|e| match e {
-        EventExt::Password(p) if p == "sec``ret" => true,
-        _ => false,
-    }));
-}
-
-#[test]
-fn add_password_events_triple_backticks() {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext("hello *world [pass````sec```ret````]*", options);
-    let evts = get_events_with_passwords(parser);
-    assert!(evts.iter().any(|e| match e {
-        EventExt::Password(p) if p == "sec```ret" => true,
-        _ => false,
-    }));
-}
-
-#[test]
-fn add_password_events_leading_trailing_backtick_space_approach() {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext("hello *world [pass`` `sec`ret` ``]*", options);
-    let evts = get_events_with_passwords(parser);
-    println!("{:?}", evts);
-    assert!(evts.iter().any(|e| match e {
-        EventExt::Password(p) if p == "`sec`ret`" => true,
-        _ => false,
-    }));
-}
-
-#[test]
-fn incomplete_passwords_dont_drop_items() {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = Parser::new_ext("hello *world [pass`secret]*", options);
-    let evts = get_events_with_passwords(parser);
-    assert_eq!(
-        "hello world [pass`secret]",
-        evts.iter()
-            .filter_map(|e| match e {
-                EventExt::StandardEvent(Event::Text(t)) => Some(t.to_string()),
-                _ => None,
-            })
-            .fold("".to_string(), |sofar, cur| sofar + &cur)
-    );
-}
@@ -1,5 +1,5 @@
 use crate::{
-    notes, perform_insert_or_update,
+    perform_insert_or_update,
     sql_thread::SqlFunc,
     sql_util,
     widgets::{
@@ -2250,7 +2250,7 @@ fn server_note_contents_show(
     focused_server_item_id: Option<i32>,
     vbox: &gtk::Box,
 ) -> adw::PreferencesGroup {
-    let contents_head = notes::note_markdown_to_quick_preview(&note.contents)
+    let contents_head = projectpadsql::notes::note_markdown_to_quick_preview(&note.contents)
         .lines()
         .take(3)
         .collect_vec()
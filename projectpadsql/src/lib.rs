@@ -2,6 +2,7 @@
 extern crate diesel;
 
 pub mod models;
+pub mod notes;
 pub mod schema;
 
 use diesel::connection::SimpleConnection;
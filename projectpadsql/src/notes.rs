@@ -0,0 +1,257 @@
+// markdown notes helpers shared between the GUI and ppcli
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+
+// cmark parses the passwords like so:
+// Text(Borrowed("[")) <-- opening bracket
+// Text(Borrowed("pass|XXX|")) <-- body
+// Text(Borrowed("]")) <-- closing bracket
+#[derive(PartialEq, Debug, Clone)]
+enum PassState<'a> {
+    None,
+    AfterOpeningBracket(EventExt<'a>),
+    AfterPass(Vec<EventExt<'a>>),
+    AfterBody(Vec<EventExt<'a>>, String),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum EventExt<'a> {
+    StandardEvent(Event<'a>),
+    Password(String),
+}
+
+pub fn get_events_with_passwords(parser: Parser) -> Vec<EventExt> {
+    let mut pass_state = PassState::None;
+    parser.fold(vec![], |mut so_far, evt| match (&pass_state, evt) {
+        (_, Event::Text(v)) if v.as_ref() == "[" => {
+            pass_state = PassState::AfterOpeningBracket(EventExt::StandardEvent(Event::Text(v)));
+            so_far
+        }
+        (PassState::AfterOpeningBracket(e0), Event::Text(v)) if v.as_ref() == "pass" => {
+            pass_state = PassState::AfterPass(vec![
+                e0.clone(),
+                EventExt::StandardEvent(Event::Text(v.clone())),
+            ]);
+            so_far
+        }
+        (PassState::AfterPass(vec0), Event::Code(ref v)) => {
+            let pass = v.to_string();
+            let mut vec = vec0.clone();
+            vec.push(EventExt::StandardEvent(Event::Code(v.clone())));
+            pass_state = PassState::AfterBody(vec, pass);
+            so_far
+        }
+        (PassState::AfterBody(_, p), Event::Text(v)) if v.as_ref() == "]" => {
+            so_far.push(EventExt::Password(p.clone()));
+            pass_state = PassState::None;
+            so_far
+        }
+        (ps, evt) => {
+            // in case we were in the process of parsing a password and the parsing
+            // didn't conclude positively, flush back the events that I held back
+            match ps {
+                PassState::AfterOpeningBracket(e) => so_far.push(e.clone()),
+                PassState::AfterPass(es) => so_far.extend(es.clone()),
+                PassState::AfterBody(es, _) => so_far.extend(es.clone()),
+                _ => {}
+            }
+            pass_state = PassState::None;
+            so_far.push(EventExt::StandardEvent(evt.clone()));
+            so_far
+        }
+    })
+}
+
+pub fn note_markdown_to_quick_preview(input: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext(input, options);
+    let events_with_passwords = get_events_with_passwords(parser);
+    let mut result = "".to_string();
+    for event in events_with_passwords {
+        match event {
+            EventExt::StandardEvent(Event::Text(t)) => result.push_str(&t),
+            EventExt::StandardEvent(Event::Code(t)) => result.push_str(&t),
+            EventExt::StandardEvent(Event::Start(Tag::Paragraph)) => result.push('\n'),
+            EventExt::StandardEvent(Event::End(Tag::Paragraph)) => result.push('\n'),
+            EventExt::StandardEvent(Event::End(Tag::Heading(_))) => result.push('\n'),
+            EventExt::Password(_) => result.push_str("[password]"),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// the contents of the fenced code blocks of the note, usually
+/// commands to run
+pub fn note_code_blocks(input: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut code_block: Option<String> = None;
+    for event in Parser::new_ext(input, Options::empty()) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+                code_block = Some("".to_string())
+            }
+            Event::Text(t) => {
+                if let Some(block) = code_block.as_mut() {
+                    block.push_str(&t);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some(block) = code_block.take().filter(|b| !b.trim().is_empty()) {
+                    result.push(block.trim_end().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+/// the note as text for the terminal: the markdown structure (headings,
+/// lists, code blocks) stays visible, and the passwords are hidden
+pub fn note_markdown_to_plain_text(input: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext(input, options);
+    let mut result = "".to_string();
+    let mut list_depth = 0;
+    let mut code_block: Option<String> = None;
+    for event in get_events_with_passwords(parser) {
+        match event {
+            EventExt::StandardEvent(Event::Text(t)) if code_block.is_some() => {
+                code_block.as_mut().unwrap().push_str(&t)
+            }
+            EventExt::StandardEvent(Event::Start(Tag::CodeBlock(_))) => {
+                code_block = Some("".to_string())
+            }
+            EventExt::StandardEvent(Event::End(Tag::CodeBlock(_))) => {
+                for line in code_block.take().unwrap_or_default().lines() {
+                    result.push_str(&format!("    {}\n", line));
+                }
+                result.push('\n');
+            }
+            EventExt::StandardEvent(Event::Start(Tag::Heading(level))) => {
+                result.push_str(&format!("{} ", "#".repeat(level as usize)))
+            }
+            EventExt::StandardEvent(Event::End(Tag::Heading(_)))
+            | EventExt::StandardEvent(Event::End(Tag::Paragraph)) => result.push_str("\n\n"),
+            EventExt::StandardEvent(Event::Start(Tag::List(_))) => {
+                if list_depth > 0 && !result.ends_with('\n') {
+                    result.push('\n');
+                }
+                list_depth += 1;
+            }
+            EventExt::StandardEvent(Event::End(Tag::List(_))) => {
+                list_depth -= 1;
+                if list_depth == 0 {
+                    result.push('\n');
+                }
+            }
+            EventExt::StandardEvent(Event::Start(Tag::Item)) => {
+                result.push_str(&format!("{}- ", "  ".repeat(list_depth - 1)))
+            }
+            EventExt::StandardEvent(Event::End(Tag::Item)) if !result.ends_with('\n') => {
+                result.push('\n')
+            }
+            EventExt::StandardEvent(Event::Text(t)) => result.push_str(&t),
+            EventExt::StandardEvent(Event::Code(t)) => result.push_str(&t),
+            EventExt::StandardEvent(Event::SoftBreak)
+            | EventExt::StandardEvent(Event::HardBreak) => result.push('\n'),
+            EventExt::StandardEvent(Event::Rule) => result.push_str("----\n\n"),
+            EventExt::Password(_) => result.push_str("[password]"),
+            _ => {}
+        }
+    }
+    format!("{}\n", result.trim_end())
+}
+
+#[test]
+fn note_code_blocks_and_plain_text() {
+    let note = "# Restart\n\nsteps:\n- stop [pass`secret`]\n- start\n\n```sh\nsystemctl restart app\njournalctl -f\n```\n\n    indented\n";
+    assert_eq!(
+        vec!["systemctl restart app\njournalctl -f".to_string()],
+        note_code_blocks(note)
+    );
+    assert_eq!(
+        "# Restart\n\nsteps:\n\n- stop [password]\n- start\n\n    systemctl restart app\n    journalctl -f\n\n    indented\n",
+        note_markdown_to_plain_text(note)
+    );
+}
+
+#[test]
+fn add_password_events() {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext("hello *world [pass`se*c~~r*et`]*", options);
+    let evts = get_events_with_passwords(parser);
+    assert!(evts.iter().any(|e| match e {
+        EventExt::Password(p) if p == "se*c~~r*et" => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn add_password_events_backticks() {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext("hello *world [pass``sec`ret``]*", options);
+    let evts = get_events_with_passwords(parser);
+    assert!(evts.iter().any(|e| match e {
+        EventExt::Password(p) if p == "sec`ret" => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn add_password_events_double_backticks() {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext("hello *world [pass```sec``ret```]*", options);
+    let evts = get_events_with_passwords(parser);
+    assert!(evts.iter().any(|e| match e {
+        EventExt::Password(p) if p == "sec``ret" => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn add_password_events_triple_backticks() {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext("hello *world [pass````sec```ret````]*", options);
+    let evts = get_events_with_passwords(parser);
+    assert!(evts.iter().any(|e| match e {
+        EventExt::Password(p) if p == "sec```ret" => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn add_password_events_leading_trailing_backtick_space_approach() {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext("hello *world [pass`` `sec`ret` ``]*", options);
+    let evts = get_events_with_passwords(parser);
+    println!("{:?}", evts);
+    assert!(evts.iter().any(|e| match e {
+        EventExt::Password(p) if p == "`sec`ret`" => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn incomplete_passwords_dont_drop_items() {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext("hello *world [pass`secret]*", options);
+    let evts = get_events_with_passwords(parser);
+    assert_eq!(
+        "hello world [pass`secret]",
+        evts.iter()
+            .filter_map(|e| match e {
+                EventExt::StandardEvent(Event::Text(t)) => Some(t.to_string()),
+                _ => None,
+            })
+            .fold("".to_string(), |sofar, cur| sofar + &cur)
+    );
+}
@@ -153,12 +153,19 @@ joinable!(server_database -> server (server_id));
 joinable!(project_point_of_interest -> project (project_id));
 allow_tables_to_appear_in_same_query!(project, project_point_of_interest);
 
+joinable!(project_note -> project (project_id));
+allow_tables_to_appear_in_same_query!(project, project_note);
+
 joinable!(server -> project (project_id));
 joinable!(server_point_of_interest -> server (server_id));
+joinable!(server_extra_user_account -> server (server_id));
+joinable!(server_note -> server (server_id));
 allow_tables_to_appear_in_same_query!(
     project,
     server_point_of_interest,
     server_website,
     server_database,
+    server_extra_user_account,
+    server_note,
     server
 );